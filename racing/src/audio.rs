@@ -0,0 +1,227 @@
+//! Engine and tire sounds.
+//!
+//! Every sound is synthesized when the demo starts, so there are no audio assets:
+//!
+//! - a looping engine hum, played faster and louder the faster the player goes,
+//! - looping tire noise that fades in once the car slides sideways,
+//! - a thud for every hard hit against a wall.
+//!
+//! Output goes through rodio and is behind the `audio` feature so headless builds don't need
+//! the backend. If there is no output device the demo carries on silently.
+
+use crate::{
+    math,
+    vehicle::{Vehicle, TOP_SPEED},
+};
+
+/// Engine playback speed standing still and at top speed. Playing it faster raises the pitch.
+const ENGINE_PITCH: (f32, f32) = (0.6, 2.2);
+/// Engine volume standing still and at top speed.
+const ENGINE_VOLUME: (f32, f32) = (0.15, 0.4);
+/// Sideways speed, in units per second, at which the tires start to screech.
+const SLIP_THRESHOLD: f32 = 1.2;
+/// Sideways speed past [`SLIP_THRESHOLD`] at which the screech is at full volume.
+const SLIP_RANGE: f32 = 1.5;
+const SCREECH_VOLUME: f32 = 0.3;
+/// Impact speed below which a contact is just the car resting or grinding against a wall.
+const THUD_THRESHOLD: f32 = 1.5;
+/// Impact speed of a thud at full volume.
+const THUD_FULL: f32 = 8.0;
+/// Seconds after a thud before the next one can play, so bouncing along a wall doesn't
+/// machine-gun the speakers.
+const THUD_COOLDOWN: f32 = 0.2;
+
+pub struct Audio {
+    output: output::Output,
+    /// Seconds until the next thud may play.
+    cooldown: f32,
+}
+
+impl Audio {
+    /// Opens the default output device and starts the looping sounds.
+    pub fn new() -> Self {
+        Self {
+            output: output::Output::new(),
+            cooldown: 0.0,
+        }
+    }
+
+    /// Follows the player's car with the engine and tire sounds. Call once per frame.
+    pub fn update(&mut self, vehicle: &Vehicle, dt: f32) {
+        self.cooldown = (self.cooldown - dt).max(0.0);
+
+        let forward = math::forward(vehicle.heading);
+        let speed = (vehicle.velocity.dot(forward).abs() / TOP_SPEED).min(1.0);
+        let lerp = |(a, b): (f32, f32)| a + (b - a) * speed;
+        self.output.engine(lerp(ENGINE_PITCH), lerp(ENGINE_VOLUME));
+
+        let slip = vehicle.velocity.dot(math::left(vehicle.heading)).abs();
+        let screech = ((slip - SLIP_THRESHOLD) / SLIP_RANGE).clamp(0.0, 1.0);
+        self.output.screech(screech * SCREECH_VOLUME);
+    }
+
+    /// Plays a thud for a collision that hit the car at `impact_speed`, see
+    /// [`crate::collision::Contact`].
+    pub fn impact(&mut self, impact_speed: f32) {
+        if impact_speed < THUD_THRESHOLD || self.cooldown > 0.0 {
+            return;
+        }
+        self.cooldown = THUD_COOLDOWN;
+        self.output.thud((impact_speed / THUD_FULL).min(1.0));
+    }
+
+    /// Silences the looping sounds until the next [`Audio::update`].
+    pub fn mute(&mut self) {
+        self.output.engine(1.0, 0.0);
+        self.output.screech(0.0);
+    }
+}
+
+#[cfg(feature = "audio")]
+mod output {
+    use std::f32::consts::TAU;
+
+    use rodio::{buffer::SamplesBuffer, OutputStream, OutputStreamHandle, Sink, Source};
+
+    const SAMPLE_RATE: u32 = 44_100;
+    /// Lowest engine harmonic in Hz, before [`Sink::set_speed`] shifts it.
+    const ENGINE_HZ: f32 = 50.0;
+
+    /// The default output device with a sink per looping sound.
+    pub struct Output {
+        device: Option<Device>,
+    }
+
+    struct Device {
+        // the stream stops playing when dropped
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+        engine: Sink,
+        screech: Sink,
+        thud: SamplesBuffer<f32>,
+    }
+
+    impl Output {
+        pub fn new() -> Self {
+            let device = match Device::open() {
+                Ok(device) => Some(device),
+                Err(e) => {
+                    tracing::warn!("Audio unavailable: {e}");
+                    None
+                }
+            };
+            Self { device }
+        }
+
+        pub fn engine(&self, speed: f32, volume: f32) {
+            if let Some(device) = &self.device {
+                device.engine.set_speed(speed);
+                device.engine.set_volume(volume);
+            }
+        }
+
+        pub fn screech(&self, volume: f32) {
+            if let Some(device) = &self.device {
+                device.screech.set_volume(volume);
+            }
+        }
+
+        pub fn thud(&self, volume: f32) {
+            if let Some(device) = &self.device {
+                let thud = device.thud.clone().amplify(volume);
+                if let Err(e) = device.handle.play_raw(thud) {
+                    tracing::warn!("Failed to play thud: {e}");
+                }
+            }
+        }
+    }
+
+    impl Device {
+        fn open() -> Result<Self, Box<dyn std::error::Error>> {
+            let (stream, handle) = OutputStream::try_default()?;
+            let engine = looping(&handle, engine())?;
+            let screech = looping(&handle, noise(SAMPLE_RATE as usize))?;
+            engine.set_volume(0.0);
+            screech.set_volume(0.0);
+            Ok(Self {
+                _stream: stream,
+                handle,
+                engine,
+                screech,
+                thud: buffer(thud()),
+            })
+        }
+    }
+
+    fn looping(handle: &OutputStreamHandle, samples: Vec<f32>) -> Result<Sink, rodio::PlayError> {
+        let sink = Sink::try_new(handle)?;
+        sink.append(buffer(samples).repeat_infinite());
+        Ok(sink)
+    }
+
+    fn buffer(samples: Vec<f32>) -> SamplesBuffer<f32> {
+        SamplesBuffer::new(1, SAMPLE_RATE, samples)
+    }
+
+    /// One second of a rough engine note: a few harmonics of [`ENGINE_HZ`] with a little
+    /// noise. Every harmonic fits the second a whole number of times, so it loops cleanly.
+    fn engine() -> Vec<f32> {
+        let noise = noise(SAMPLE_RATE as usize);
+        (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let tone: f32 = [(1.0, 0.5), (2.0, 0.3), (3.0, 0.15), (4.0, 0.1)]
+                    .iter()
+                    .map(|&(harmonic, gain)| (TAU * ENGINE_HZ * harmonic * t).sin() * gain)
+                    .sum();
+                tone + noise[i] * 0.05
+            })
+            .collect()
+    }
+
+    /// A quarter second of a low thump that dies away quickly.
+    fn thud() -> Vec<f32> {
+        let len = SAMPLE_RATE as usize / 4;
+        noise(len)
+            .into_iter()
+            .enumerate()
+            .map(|(i, noise)| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let envelope = (-t * 25.0).exp();
+                ((TAU * 70.0 * t).sin() * 0.8 + noise * 0.3) * envelope
+            })
+            .collect()
+    }
+
+    /// `len` samples of white noise in `[-1, 1]` from a xorshift generator, which is plenty
+    /// random for tire hiss.
+    fn noise(len: usize) -> Vec<f32> {
+        let mut state: u32 = 0x2545_f491;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as f32 / u32::MAX as f32 * 2.0 - 1.0
+            })
+            .collect()
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod output {
+    /// Stand-in used when the demo is built without audio support.
+    pub struct Output;
+
+    impl Output {
+        pub fn new() -> Self {
+            Self
+        }
+
+        pub fn engine(&self, _speed: f32, _volume: f32) {}
+
+        pub fn screech(&self, _volume: f32) {}
+
+        pub fn thud(&self, _volume: f32) {}
+    }
+}
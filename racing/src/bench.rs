@@ -0,0 +1,296 @@
+//! Benchmark mode, for profiling rivik with a reproducible workload.
+//!
+//! `--bench` skips the menu and races for [`SECONDS`] of simulated time with every player's
+//! car on autopilot, so a run doesn't depend on anyone at the keyboard or on the window
+//! having focus. Every frame simulates exactly [`FRAME`] seconds however long it really
+//! took, which keeps the work done identical between runs. When the time is up a report with
+//! one row per frame is written to `--bench-out <file>` ([`DEFAULT_OUT`] if not given, JSON
+//! if the name ends in `.json`) and the demo exits.
+//!
+//! Frames are cut at the start of every `App::update`, so a row covers one update and the
+//! render after it. Each row holds the wall-clock frame time, the time spent in `App::update`
+//! and the time spent in every tracing span entered during the frame, which includes rivik's
+//! own render spans. rivik doesn't expose GPU timestamps, so GPU work only shows up in the
+//! frame times. Escape doesn't pause a benchmark.
+//!
+//! `--scale <n>`, which only applies together with `--bench`, adds `n` more AI cars spread
+//! around the racing line and `n` copies of the track's solid props stacked above the
+//! originals, to stress-test draw calls.
+
+use std::{
+    fmt::Write,
+    fs, io,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use glam::{Mat4, Vec3};
+use tracing::{span, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::{
+    route::Route,
+    track::{Collision, Spawn, Track},
+};
+
+/// Simulated seconds a benchmark runs for.
+pub const SECONDS: f32 = 60.0;
+/// Simulated seconds per frame.
+pub const FRAME: f32 = 1.0 / 60.0;
+/// Report written when no `--bench-out` is given.
+pub const DEFAULT_OUT: &str = "bench.csv";
+/// Fraction of top speed the autopilot drives at.
+pub const PACE: f32 = 0.9;
+
+/// Height between the stacked copies of a prop.
+const PROP_STACK: f32 = 10.0;
+
+/// Time spent in each span since the last frame was recorded, filled in by [`SpanTimes`].
+static SPANS: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+
+struct Frame {
+    frame: f32,
+    update: f32,
+    spans: Vec<(&'static str, Duration)>,
+}
+
+pub struct Bench {
+    out: PathBuf,
+    frames: Vec<Frame>,
+    /// Seconds spent in `App::update` in the frame in progress, `None` until the first one.
+    update: Option<f32>,
+}
+
+impl Bench {
+    pub fn new(out: PathBuf) -> Self {
+        Self {
+            out,
+            frames: Vec::new(),
+            update: None,
+        }
+    }
+
+    /// Ends the frame in progress, which took `frame` seconds. Call first thing in
+    /// `App::update`, right after the clock ticks. The first call only throws away what was
+    /// measured while starting up. Returns whether the benchmark is over.
+    pub fn frame(&mut self, frame: f32) -> bool {
+        let spans = std::mem::take(&mut *SPANS.lock().unwrap());
+        if let Some(update) = self.update.take() {
+            self.frames.push(Frame {
+                frame,
+                update,
+                spans,
+            });
+        }
+        self.frames.len() as f32 * FRAME >= SECONDS
+    }
+
+    /// Records that `App::update` took `update` seconds in the frame in progress.
+    pub fn updated(&mut self, update: f32) {
+        self.update = Some(update);
+    }
+
+    /// Writes the report and logs a summary.
+    pub fn finish(&self) {
+        let mut times: Vec<f32> = self.frames.iter().map(|f| f.frame).collect();
+        times.sort_by(f32::total_cmp);
+        let mean = times.iter().sum::<f32>() / times.len().max(1) as f32;
+        let p99 = times
+            .get(times.len() * 99 / 100)
+            .copied()
+            .unwrap_or_default();
+        tracing::info!(
+            "Benchmark done: {} frames, mean {:.3} ms, 99th percentile {:.3} ms",
+            times.len(),
+            mean * 1000.0,
+            p99 * 1000.0
+        );
+
+        let report = if self.out.extension().is_some_and(|ext| ext == "json") {
+            self.json()
+        } else {
+            self.csv()
+        };
+        match fs::write(&self.out, report) {
+            Ok(()) => tracing::info!("Wrote benchmark report to {}", self.out.display()),
+            Err(e) => tracing::error!("Failed to write {}: {e}", self.out.display()),
+        }
+    }
+
+    /// Names of every span seen during the run, in the order they first showed up.
+    fn span_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        for (name, _) in self.frames.iter().flat_map(|f| &f.spans) {
+            if !names.contains(name) {
+                names.push(*name);
+            }
+        }
+        names
+    }
+
+    fn csv(&self) -> String {
+        let names = self.span_names();
+        let mut out = String::from("frame,frame_ms,update_ms");
+        for name in &names {
+            write!(out, ",{name}_ms").unwrap();
+        }
+        out.push('\n');
+        for (i, frame) in self.frames.iter().enumerate() {
+            write!(out, "{i},{:.3},{:.3}", ms(frame.frame), ms(frame.update)).unwrap();
+            for name in &names {
+                write!(out, ",{:.3}", frame.span(name)).unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn json(&self) -> String {
+        let mut out = format!("{{\"frame_seconds\":{FRAME},\"frames\":[");
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write!(
+                out,
+                "{{\"frame_ms\":{:.3},\"update_ms\":{:.3},\"spans\":{{",
+                ms(frame.frame),
+                ms(frame.update)
+            )
+            .unwrap();
+            for (j, (name, time)) in frame.spans.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                push_json_string(&mut out, name);
+                write!(out, ":{:.3}", ms(time.as_secs_f32())).unwrap();
+            }
+            out.push_str("}}");
+        }
+        out.push_str("]}\n");
+        out
+    }
+}
+
+impl Frame {
+    /// Milliseconds spent in the span called `name` during this frame.
+    fn span(&self, name: &str) -> f32 {
+        self.spans
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map_or(0.0, |(_, time)| ms(time.as_secs_f32()))
+    }
+}
+
+fn ms(seconds: f32) -> f32 {
+    seconds * 1000.0
+}
+
+/// Appends `text` to `out` as a quoted JSON string.
+fn push_json_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Tracing layer adding up how long every span is entered for. Only installed with
+/// `--bench`, next to rivik's `UiSubscriber`.
+pub struct SpanTimes;
+
+/// When the span was last entered.
+struct Entered(Instant);
+
+impl<S> Layer<S> for SpanTimes
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().replace(Entered(Instant::now()));
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let Some(Entered(start)) = span.extensions_mut().remove::<Entered>() else {
+            return;
+        };
+        let elapsed = start.elapsed();
+        let mut spans = SPANS.lock().unwrap();
+        match spans.iter_mut().find(|(name, _)| *name == span.name()) {
+            Some((_, time)) => *time += elapsed,
+            None => spans.push((span.name(), elapsed)),
+        }
+    }
+}
+
+/// `count` slots spread evenly around the racing line, facing along it. They aren't on the
+/// grid, so every car starts on the lap it's already on.
+pub fn scale_grid(route: &Route, count: usize) -> Vec<Spawn> {
+    (0..count)
+        .map(|i| {
+            let distance = route.length() * (i as f32 + 0.5) / count as f32;
+            let position = route.point_at(distance);
+            let to = route.point_at(distance + 1.0) - position;
+            Spawn {
+                position: [position.x, position.y, position.z],
+                heading: to.x.atan2(to.z).to_degrees(),
+                lap: Some(0),
+            }
+        })
+        .collect()
+}
+
+/// Inserts `count` copies of every scenery mesh with walls, stacked above the original.
+/// The copies are only for show and don't take part in collision.
+pub fn load_props(ctx: &mut rivik::Context, track: &Track, count: usize) -> io::Result<()> {
+    let props = track
+        .meshes
+        .iter()
+        .filter(|scenery| scenery.collision == Collision::Walls);
+    for scenery in props {
+        for i in 1..=count {
+            let lift = Mat4::from_translation(Vec3::Y * PROP_STACK * i as f32);
+            ctx.insert(crate::load_mesh(&scenery.mesh, &scenery.texture)?)
+                .transform(ctx)
+                .write()
+                .unwrap()
+                .update(lift * scenery.transform());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_string(text: &str) -> String {
+        let mut out = String::new();
+        push_json_string(&mut out, text);
+        out
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("render"), r#""render""#);
+        assert_eq!(json_string(r#"say "hi"\"#), r#""say \"hi\"\\""#);
+        assert_eq!(json_string("a\nb\t"), r#""a\nb\t""#);
+        // Debug would write \u{1}, which isn't JSON
+        assert_eq!(json_string("\u{1}"), r#""\u0001""#);
+        assert_eq!(json_string("caf\u{e9}"), "\"caf\u{e9}\"");
+    }
+}
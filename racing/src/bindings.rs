@@ -0,0 +1,427 @@
+//! Rebindable controls, read from `controls.toml`.
+//!
+//! Every action takes a list of key names, any of which triggers it:
+//!
+//! ```toml
+//! [keyboard]
+//! steer_left = ["A"]
+//! steer_right = ["D"]
+//! throttle = ["W"]
+//! brake = ["S"]
+//! handbrake = ["Space"]
+//!
+//! # the second car in two-player races, arrow keys by default
+//! [player_two]
+//! handbrake = ["RControl", "RShift"]
+//!
+//! [gamepad]
+//! dead_zone = 0.1
+//! # "linear", "squared", which gives finer control around the center, or a custom
+//! # exponent like { exponent = 1.5 }
+//! curve = "squared"
+//! ```
+//!
+//! Key names are winit's `VirtualKeyCode` variant names. Anything left out of the file keeps
+//! its default, and a missing or broken file falls back to the defaults entirely.
+
+use std::{fs, io, path::Path};
+
+use rivik::winit::event::VirtualKeyCode;
+use serde::Deserialize;
+
+/// Default keys of the first player, in the order steer left, steer right, throttle, brake,
+/// handbrake.
+const PLAYER_ONE: [&str; 5] = ["A", "D", "W", "S", "Space"];
+/// Default keys of the second player, in the same order as [`PLAYER_ONE`].
+const PLAYER_TWO: [&str; 5] = ["Left", "Right", "Up", "Down", "RControl"];
+/// Dead zone used when the file doesn't set a usable one.
+const DEFAULT_DEAD_ZONE: f32 = 0.1;
+
+pub struct Bindings {
+    /// Keys of the first player, who also gets the gamepad.
+    pub keyboard: Keys,
+    /// Keys of the second player in two-player races.
+    pub player_two: Keys,
+    /// Stick and trigger travel, as a fraction of full deflection, that is ignored.
+    pub dead_zone: f32,
+    /// Response of the sticks and triggers past the dead zone.
+    pub curve: Curve,
+}
+
+/// How stick and trigger deflection maps onto the driving axes. Every curve keeps full
+/// deflection at full strength.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Curve {
+    #[default]
+    Linear,
+    /// Deflection squared, keeping its sign, so small movements steer more gently.
+    Squared,
+    /// Deflection raised to a power, keeping its sign. Above 1 is gentler around the center,
+    /// below 1 is twitchier.
+    Exponent(f32),
+}
+
+impl Curve {
+    /// Shapes a deflection in `[-1, 1]`.
+    pub fn apply(self, value: f32) -> f32 {
+        match self {
+            Curve::Linear => value,
+            Curve::Squared => value * value.abs(),
+            Curve::Exponent(power) => value.signum() * value.abs().powf(power),
+        }
+    }
+
+    /// Exponents that are zero, negative or not a number would stop full deflection from
+    /// reaching 1.
+    fn is_valid(self) -> bool {
+        match self {
+            Curve::Exponent(power) => power.is_finite() && power > 0.0,
+            Curve::Linear | Curve::Squared => true,
+        }
+    }
+}
+
+pub struct Keys {
+    pub steer_left: Vec<VirtualKeyCode>,
+    pub steer_right: Vec<VirtualKeyCode>,
+    pub throttle: Vec<VirtualKeyCode>,
+    pub brake: Vec<VirtualKeyCode>,
+    pub handbrake: Vec<VirtualKeyCode>,
+}
+
+impl Bindings {
+    /// Reads bindings from `path`, warning about and skipping anything it can't use.
+    pub fn load(path: &Path) -> Self {
+        let src = match fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    tracing::warn!("Failed to read {}: {e}", path.display());
+                }
+                return Self::from(ControlsFile::default());
+            }
+        };
+        match toml::from_str::<ControlsFile>(&src) {
+            Ok(file) => Self::from(file),
+            Err(e) => {
+                tracing::warn!("Ignoring invalid {}: {e}", path.display());
+                Self::from(ControlsFile::default())
+            }
+        }
+    }
+}
+
+impl From<ControlsFile> for Bindings {
+    fn from(file: ControlsFile) -> Self {
+        Self {
+            keyboard: Keys::new(file.keyboard, PLAYER_ONE),
+            player_two: Keys::new(file.player_two, PLAYER_TWO),
+            dead_zone: file.gamepad.dead_zone(),
+            curve: file.gamepad.curve(),
+        }
+    }
+}
+
+impl Keys {
+    /// Parses the keys of one player, taking anything the file leaves out from `defaults`.
+    /// An action whose keys are all unknown keeps its default too, an empty list unbinds it.
+    fn new(file: KeyboardFile, defaults: [&str; 5]) -> Self {
+        let keys = |names: Option<Vec<String>>, default: &str| -> Vec<VirtualKeyCode> {
+            let default = || vec![parse_key(default).expect("default keys are valid")];
+            let Some(names) = names else {
+                return default();
+            };
+            let keys: Vec<_> = names
+                .iter()
+                .filter_map(|name| {
+                    let key = parse_key(name);
+                    if key.is_none() {
+                        tracing::warn!("Unknown key in controls: {name}");
+                    }
+                    key
+                })
+                .collect();
+            if keys.is_empty() && !names.is_empty() {
+                default()
+            } else {
+                keys
+            }
+        };
+        let [steer_left, steer_right, throttle, brake, handbrake] = defaults;
+        Self {
+            steer_left: keys(file.steer_left, steer_left),
+            steer_right: keys(file.steer_right, steer_right),
+            throttle: keys(file.throttle, throttle),
+            brake: keys(file.brake, brake),
+            handbrake: keys(file.handbrake, handbrake),
+        }
+    }
+}
+
+/// Layout of `controls.toml`.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ControlsFile {
+    keyboard: KeyboardFile,
+    player_two: KeyboardFile,
+    gamepad: GamepadFile,
+}
+
+/// Keys of one player. Whatever is left out keeps that player's default.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeyboardFile {
+    steer_left: Option<Vec<String>>,
+    steer_right: Option<Vec<String>>,
+    throttle: Option<Vec<String>>,
+    brake: Option<Vec<String>>,
+    handbrake: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct GamepadFile {
+    dead_zone: f32,
+    curve: Curve,
+}
+
+impl GamepadFile {
+    fn dead_zone(&self) -> f32 {
+        if !self.dead_zone.is_finite() {
+            tracing::warn!("Ignoring invalid dead zone in controls: {}", self.dead_zone);
+            return DEFAULT_DEAD_ZONE;
+        }
+        self.dead_zone.clamp(0.0, 0.99)
+    }
+
+    fn curve(&self) -> Curve {
+        if !self.curve.is_valid() {
+            tracing::warn!(
+                "Ignoring invalid response curve in controls: {:?}",
+                self.curve
+            );
+            return Curve::default();
+        }
+        self.curve
+    }
+}
+
+impl Default for GamepadFile {
+    fn default() -> Self {
+        Self {
+            dead_zone: DEFAULT_DEAD_ZONE,
+            curve: Curve::default(),
+        }
+    }
+}
+
+/// Looks up a key by its `VirtualKeyCode` name. Single digits are accepted as a shorthand
+/// for `Key0`..`Key9`.
+fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+
+    let key = match name {
+        "A" => A,
+        "B" => B,
+        "C" => C,
+        "D" => D,
+        "E" => E,
+        "F" => F,
+        "G" => G,
+        "H" => H,
+        "I" => I,
+        "J" => J,
+        "K" => K,
+        "L" => L,
+        "M" => M,
+        "N" => N,
+        "O" => O,
+        "P" => P,
+        "Q" => Q,
+        "R" => R,
+        "S" => S,
+        "T" => T,
+        "U" => U,
+        "V" => V,
+        "W" => W,
+        "X" => X,
+        "Y" => Y,
+        "Z" => Z,
+        "0" | "Key0" => Key0,
+        "1" | "Key1" => Key1,
+        "2" | "Key2" => Key2,
+        "3" | "Key3" => Key3,
+        "4" | "Key4" => Key4,
+        "5" | "Key5" => Key5,
+        "6" | "Key6" => Key6,
+        "7" | "Key7" => Key7,
+        "8" | "Key8" => Key8,
+        "9" | "Key9" => Key9,
+        "F1" => F1,
+        "F2" => F2,
+        "F3" => F3,
+        "F4" => F4,
+        "F5" => F5,
+        "F6" => F6,
+        "F7" => F7,
+        "F8" => F8,
+        "F9" => F9,
+        "F10" => F10,
+        "F11" => F11,
+        "F12" => F12,
+        "Left" => Left,
+        "Right" => Right,
+        "Up" => Up,
+        "Down" => Down,
+        "Space" => Space,
+        "Return" => Return,
+        "Back" => Back,
+        "Tab" => Tab,
+        "Escape" => Escape,
+        "Insert" => Insert,
+        "Delete" => Delete,
+        "Home" => Home,
+        "End" => End,
+        "PageUp" => PageUp,
+        "PageDown" => PageDown,
+        "LShift" => LShift,
+        "RShift" => RShift,
+        "LControl" => LControl,
+        "RControl" => RControl,
+        "LAlt" => LAlt,
+        "RAlt" => RAlt,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use VirtualKeyCode::*;
+
+    /// A file in the temp directory that is removed again when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &str) -> Self {
+            let name = format!("racing-{}-{name}.toml", std::process::id());
+            let file = Self(std::env::temp_dir().join(name));
+            fs::write(&file.0, contents).unwrap();
+            file
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn assert_defaults(bindings: &Bindings) {
+        let one = &bindings.keyboard;
+        assert_eq!(one.steer_left, [A]);
+        assert_eq!(one.steer_right, [D]);
+        assert_eq!(one.throttle, [W]);
+        assert_eq!(one.brake, [S]);
+        assert_eq!(one.handbrake, [Space]);
+        let two = &bindings.player_two;
+        assert_eq!(two.steer_left, [Left]);
+        assert_eq!(two.handbrake, [RControl]);
+        assert_eq!(bindings.dead_zone, DEFAULT_DEAD_ZONE);
+        assert_eq!(bindings.curve, Curve::Linear);
+    }
+
+    #[test]
+    fn default_keys_parse() {
+        for name in PLAYER_ONE.iter().chain(&PLAYER_TWO) {
+            assert!(parse_key(name).is_some(), "{name}");
+        }
+        assert_eq!(parse_key("7"), Some(Key7));
+        assert_eq!(parse_key("Key7"), Some(Key7));
+        assert_eq!(parse_key("Hyperspace"), None);
+    }
+
+    #[test]
+    fn missing_or_invalid_file_uses_defaults() {
+        assert_defaults(&Bindings::load(Path::new("no-such-controls.toml")));
+        let file = TempFile::new("broken-controls", "[keyboard\nsteer_left = A");
+        assert_defaults(&Bindings::load(&file.0));
+        let file = TempFile::new("mistyped-controls", "[gamepad]\ndead_zone = \"big\"");
+        assert_defaults(&Bindings::load(&file.0));
+    }
+
+    #[test]
+    fn partial_file_only_overrides_what_it_sets() {
+        let src = "[keyboard]\nthrottle = [\"Up\", \"W\"]\n\n[gamepad]\ncurve = \"squared\"\n";
+        let file = TempFile::new("partial-controls", src);
+        let bindings = Bindings::load(&file.0);
+        assert_eq!(bindings.keyboard.throttle, [Up, W]);
+        assert_eq!(bindings.keyboard.steer_left, [A]);
+        assert_eq!(bindings.keyboard.brake, [S]);
+        assert_eq!(bindings.player_two.throttle, [Up]);
+        assert_eq!(bindings.dead_zone, DEFAULT_DEAD_ZONE);
+        assert_eq!(bindings.curve, Curve::Squared);
+    }
+
+    #[test]
+    fn unknown_keys_fall_back_to_the_default() {
+        let file = KeyboardFile {
+            steer_left: Some(vec!["Hyperspace".to_owned()]),
+            steer_right: Some(vec!["Hyperspace".to_owned(), "L".to_owned()]),
+            handbrake: Some(Vec::new()),
+            ..KeyboardFile::default()
+        };
+        let keys = Keys::new(file, PLAYER_ONE);
+        assert_eq!(keys.steer_left, [A]);
+        // known keys next to an unknown one are kept
+        assert_eq!(keys.steer_right, [L]);
+        assert_eq!(keys.throttle, [W]);
+        // an empty list unbinds the action
+        assert!(keys.handbrake.is_empty());
+    }
+
+    #[test]
+    fn curves_keep_the_ends_and_the_sign() {
+        let curves = [
+            Curve::Linear,
+            Curve::Squared,
+            Curve::Exponent(1.5),
+            Curve::Exponent(0.5),
+        ];
+        for curve in curves {
+            assert_eq!(curve.apply(0.0), 0.0, "{curve:?}");
+            assert!((curve.apply(1.0) - 1.0).abs() < 1e-6, "{curve:?}");
+            assert!((curve.apply(-1.0) + 1.0).abs() < 1e-6, "{curve:?}");
+            assert!(curve.apply(-0.5) < 0.0, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn curves_shape_the_middle() {
+        assert_eq!(Curve::Linear.apply(0.5), 0.5);
+        assert_eq!(Curve::Squared.apply(-0.5), -0.25);
+        assert!((Curve::Exponent(3.0).apply(0.5) - 0.125).abs() < 1e-6);
+    }
+
+    #[test]
+    fn invalid_gamepad_settings_fall_back() {
+        let file = GamepadFile {
+            dead_zone: f32::NAN,
+            curve: Curve::Exponent(-1.0),
+        };
+        assert_eq!(file.dead_zone(), DEFAULT_DEAD_ZONE);
+        assert_eq!(file.curve(), Curve::Linear);
+
+        let file = GamepadFile {
+            dead_zone: 5.0,
+            curve: Curve::Exponent(f32::INFINITY),
+        };
+        assert_eq!(file.dead_zone(), 0.99);
+        assert_eq!(file.curve(), Curve::Linear);
+    }
+}
@@ -0,0 +1,197 @@
+//! Camera modes.
+//!
+//! The view follows the players' cars in one of several modes, cycled with C:
+//!
+//! - **Chase**: behind and above the car, lagging a little so turns are readable.
+//! - **Hood**: fixed to the front of the first player's car, looking down the road.
+//! - **Orbit**: circles the car slowly from the side of the track.
+//! - **Free**: a free-fly debug camera for looking around the track, e.g. to place
+//!   checkpoints. Drag with the right mouse button to look around, WASD moves, Q/E go down
+//!   and up and Shift goes faster. The first player's car gets no input while flying, so the
+//!   keys can be shared with driving.
+//!
+//! Every player has a camera of their own following their car. rivik renders a single view
+//! though, so two-player races show an extra camera following all the cars instead: in chase
+//! and orbit mode it aims between them and pulls back as they spread apart to keep both in
+//! sight, and the hood camera rides on the first player's car.
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use glam::{Mat4, Vec3};
+use rivik::winit::event::VirtualKeyCode;
+
+use crate::{input::Input, math};
+
+/// Key that switches to the next mode.
+const CYCLE_KEY: VirtualKeyCode = VirtualKeyCode::C;
+
+/// Distance behind and height above the car of the chase camera.
+const CHASE_OFFSET: (f32, f32) = (2.0, 2.0);
+/// Fraction of the way to its target the chase camera moves per tick.
+const CHASE_SMOOTHING: f32 = 0.06;
+/// Hood camera position relative to the car, as distance ahead and height.
+const HOOD_OFFSET: (f32, f32) = (0.4, 1.1);
+/// Distance and height of the orbit camera from the car.
+const ORBIT_OFFSET: (f32, f32) = (8.0, 3.0);
+/// How much further back and higher the chase and orbit cameras go per unit the followed
+/// cars are spread out.
+const SPREAD_PULLBACK: (f32, f32) = (1.2, 0.6);
+/// Radians per second the orbit camera travels around the car.
+const ORBIT_SPEED: f32 = 0.3;
+/// Units per second the free camera flies, and how much faster with Shift held.
+const FLY_SPEED: f32 = 15.0;
+const FLY_BOOST: f32 = 4.0;
+/// Radians the free camera turns per pixel dragged.
+const LOOK_SENSITIVITY: f32 = 0.005;
+/// Keeps the free camera from looking straight up or down, where `look_at` breaks down.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Mode {
+    Chase,
+    Hood,
+    Orbit,
+    Free,
+}
+
+impl Mode {
+    fn next(self) -> Self {
+        match self {
+            Mode::Chase => Mode::Hood,
+            Mode::Hood => Mode::Orbit,
+            Mode::Orbit => Mode::Free,
+            Mode::Free => Mode::Chase,
+        }
+    }
+}
+
+pub struct Camera {
+    mode: Mode,
+    /// Where the camera was last frame.
+    eye: Vec3,
+    /// Direction the camera looked in last frame.
+    look: Vec3,
+    /// Angle of the orbit camera around the car.
+    orbit: f32,
+    /// Heading and pitch of the free camera.
+    yaw: f32,
+    pitch: f32,
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Chase,
+            eye: Vec3::ZERO,
+            look: Vec3::Z,
+            orbit: 0.0,
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+
+    /// Whether the free camera is in use and owns the movement keys.
+    pub fn flying(&self) -> bool {
+        self.mode == Mode::Free
+    }
+
+    /// Handles mode switching and returns the view matrix for this frame, given the position
+    /// and heading each followed car is drawn at and `dt` seconds of frame time.
+    ///
+    /// `cars` must not be empty. The first one is the car the hood camera rides on.
+    pub fn update(&mut self, input: &Input, dt: f32, cars: &[(Vec3, f32)]) -> Mat4 {
+        let (focus, heading, spread) = framing(cars);
+        if input.was_pressed(CYCLE_KEY) {
+            self.switch(focus);
+        }
+
+        let (pull_back, pull_up) = SPREAD_PULLBACK;
+        let (eye, target) = match self.mode {
+            Mode::Chase => {
+                let (back, up) = CHASE_OFFSET;
+                let (back, up) = (back + spread * pull_back, up + spread * pull_up);
+                let eye = focus - math::forward(heading) * back + Vec3::Y * up;
+                (self.eye.lerp(eye, math::damp(CHASE_SMOOTHING, dt)), focus)
+            }
+            Mode::Hood => {
+                let (position, heading) = cars[0];
+                let forward = math::forward(heading);
+                let (ahead, up) = HOOD_OFFSET;
+                let eye = position + forward * ahead + Vec3::Y * up;
+                (eye, eye + forward)
+            }
+            Mode::Orbit => {
+                self.orbit = (self.orbit + ORBIT_SPEED * dt) % TAU;
+                let (distance, up) = ORBIT_OFFSET;
+                let (distance, up) = (distance + spread * pull_back, up + spread * pull_up);
+                let eye = focus + math::forward(self.orbit) * distance + Vec3::Y * up;
+                (eye, focus)
+            }
+            Mode::Free => {
+                let eye = self.fly(input, dt);
+                (eye, eye + self.look)
+            }
+        };
+
+        self.eye = eye;
+        self.look = (target - eye).normalize_or_zero();
+        Mat4::look_at_rh(eye, target, Vec3::Y)
+    }
+
+    fn switch(&mut self, focus: Vec3) {
+        self.mode = self.mode.next();
+        tracing::info!("Camera: {:?}", self.mode);
+        match self.mode {
+            // start circling from wherever the previous camera was
+            Mode::Orbit => {
+                let from = self.eye - focus;
+                self.orbit = from.x.atan2(from.z);
+            }
+            // take off from the current view
+            Mode::Free => {
+                self.yaw = self.look.x.atan2(self.look.z);
+                self.pitch = self.look.y.clamp(-1.0, 1.0).asin();
+            }
+            Mode::Chase | Mode::Hood => {}
+        }
+    }
+
+    /// Turns the free camera with the mouse and moves it with the keys.
+    fn fly(&mut self, input: &Input, dt: f32) -> Vec3 {
+        // dragging right turns right, which is a negative turn around +Y
+        let drag = input.drag() * LOOK_SENSITIVITY;
+        self.yaw = math::wrap_angle(self.yaw - drag.x);
+        self.pitch = (self.pitch - drag.y).clamp(-MAX_PITCH, MAX_PITCH);
+        self.look = math::forward(self.yaw) * self.pitch.cos() + Vec3::Y * self.pitch.sin();
+
+        let axis = |positive, negative| {
+            input.is_held(positive) as i32 as f32 - input.is_held(negative) as i32 as f32
+        };
+        let direction = self.look * axis(VirtualKeyCode::W, VirtualKeyCode::S)
+            + math::left(self.yaw) * axis(VirtualKeyCode::A, VirtualKeyCode::D)
+            + Vec3::Y * axis(VirtualKeyCode::E, VirtualKeyCode::Q);
+        let speed = if input.is_held(VirtualKeyCode::LShift) {
+            FLY_SPEED * FLY_BOOST
+        } else {
+            FLY_SPEED
+        };
+        self.eye + direction.normalize_or_zero() * speed * dt
+    }
+}
+
+/// Point between the cars, their average heading and how far they are from that point at
+/// most.
+fn framing(cars: &[(Vec3, f32)]) -> (Vec3, f32, f32) {
+    let (total, facing) = cars.iter().fold(
+        (Vec3::ZERO, Vec3::ZERO),
+        |(total, facing), &(position, heading)| (total + position, facing + math::forward(heading)),
+    );
+    let center = total / cars.len() as f32;
+    // cars facing opposite ways cancel out, keep the first one's heading then
+    let heading = math::heading_from_velocity(facing, cars[0].1);
+    let spread = cars
+        .iter()
+        .map(|&(position, _)| position.distance(center))
+        .fold(0.0, f32::max);
+    (center, heading, spread)
+}
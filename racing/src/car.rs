@@ -0,0 +1,171 @@
+//! A car on the track, shared by the player and the AI.
+//!
+//! Bundles the simulated [`Vehicle`] with the meshes drawing it and its progress along the
+//! racing line, and runs the per-step work every car needs regardless of who is driving.
+
+use std::f32::consts::TAU;
+
+use glam::{Mat4, Vec3};
+use rivik::{render::draw::Mesh, Handle};
+
+use crate::{
+    collision::{Contact, TrackCollision},
+    math,
+    route::{Route, Tracker},
+    track::Spawn,
+    vehicle::{Chassis, Controls, Vehicle},
+};
+
+/// Fraction of speed lost per tick with every wheel on the grass.
+const OFF_ROAD_DRAG: f32 = 0.04;
+/// How far the front wheels turn at full lock, in radians. Only visual, the vehicle model
+/// doesn't know about wheel angles.
+const WHEEL_LOCK: f32 = 0.5;
+
+/// Where unused car meshes are kept. rivik can't hide or remove a mesh, so it is moved out
+/// of sight under the ground instead.
+const PARKED: Vec3 = Vec3::new(0.0, -1000.0, 0.0);
+
+/// Scene nodes of a car, see [`crate::load_car`]. The wheels are children of the body.
+pub struct CarMesh {
+    pub body: Handle<Mesh>,
+    /// In the same order as [`Chassis::wheels`].
+    pub wheels: [Handle<Mesh>; 4],
+}
+
+impl CarMesh {
+    /// Moves the car out of sight, wheels and all.
+    pub fn park(&self, ctx: &rivik::Context) {
+        self.body
+            .transform(ctx)
+            .write()
+            .unwrap()
+            .update(Mat4::from_translation(PARKED));
+    }
+}
+
+/// Where a car was drawn from, kept for interpolating between simulation ticks.
+#[derive(Clone, Copy)]
+struct Pose {
+    position: Vec3,
+    heading: f32,
+    /// Rotation of the wheels around their axles.
+    wheel_spin: f32,
+}
+
+impl Pose {
+    fn lerp(self, to: Pose, t: f32) -> Pose {
+        Pose {
+            position: self.position.lerp(to.position, t),
+            heading: self.heading + math::wrap_angle(to.heading - self.heading) * t,
+            wheel_spin: self.wheel_spin + math::wrap_angle(to.wheel_spin - self.wheel_spin) * t,
+        }
+    }
+}
+
+pub struct Car {
+    pub mesh: CarMesh,
+    pub vehicle: Vehicle,
+    pub progress: Tracker,
+    wheel_spin: f32,
+    /// Steering input of the last step.
+    steer: f32,
+    /// Pose before the last step.
+    previous: Pose,
+}
+
+impl Car {
+    /// Puts a car at a standstill in `slot`.
+    pub fn new(mesh: CarMesh, chassis: Chassis, route: &Route, slot: &Spawn) -> Self {
+        let (position, heading) = (slot.position(), slot.heading());
+        Self {
+            mesh,
+            vehicle: Vehicle::new(chassis, position, heading),
+            progress: Tracker::new(route, position, slot.lap(route)),
+            wheel_spin: 0.0,
+            steer: 0.0,
+            previous: Pose {
+                position,
+                heading,
+                wheel_spin: 0.0,
+            },
+        }
+    }
+
+    /// Puts the car back at a standstill in `slot`, for the start of a new race.
+    pub fn reset(&mut self, route: &Route, slot: &Spawn) {
+        let (position, heading) = (slot.position(), slot.heading());
+        self.vehicle = Vehicle::new(self.vehicle.chassis, position, heading);
+        self.progress = Tracker::new(route, position, slot.lap(route));
+        self.wheel_spin = 0.0;
+        self.steer = 0.0;
+        self.previous = self.pose();
+    }
+
+    /// Steps the vehicle, keeps it out of the walls and tracks its progress.
+    pub fn drive(
+        &mut self,
+        controls: &Controls,
+        dt: f32,
+        collision: &TrackCollision,
+        route: &Route,
+    ) -> Option<Contact> {
+        self.previous = self.pose();
+        self.vehicle.step(controls, dt);
+        let contact = collision.resolve(&mut self.vehicle);
+        if !collision.on_road(&self.vehicle) {
+            self.vehicle.drag(OFF_ROAD_DRAG, dt);
+        }
+        self.progress.update(route, self.vehicle.position);
+
+        // roll the wheels along with the ground
+        let forward = math::forward(self.vehicle.heading);
+        let speed = self.vehicle.velocity.dot(forward);
+        let radius = self.vehicle.chassis.wheel_radius;
+        self.wheel_spin = (self.wheel_spin + speed / radius * dt).rem_euclid(TAU);
+        self.steer = controls.steer;
+        contact
+    }
+
+    /// Distance covered along the racing line, see [`Tracker::distance`].
+    pub fn distance(&self, route: &Route) -> f32 {
+        self.progress.distance(route)
+    }
+
+    /// Position and heading `alpha` of the way from the previous step to the latest one.
+    pub fn interpolated(&self, alpha: f32) -> (Vec3, f32) {
+        let pose = self.previous.lerp(self.pose(), alpha);
+        (pose.position, pose.heading)
+    }
+
+    /// Moves the meshes to `alpha` of the way between the last two steps, spinning the wheels
+    /// and turning the front ones with the steering.
+    pub fn sync(&self, ctx: &rivik::Context, alpha: f32) {
+        let pose = self.previous.lerp(self.pose(), alpha);
+        self.mesh
+            .body
+            .transform(ctx)
+            .write()
+            .unwrap()
+            .update(Mat4::from_translation(pose.position) * Mat4::from_rotation_y(pose.heading));
+
+        let wheels = self.mesh.wheels.iter().zip(self.vehicle.chassis.wheels);
+        for (i, (wheel, offset)) in wheels.enumerate() {
+            // negative steer is left, which is a positive turn around +Y
+            let yaw = if i < 2 { -self.steer * WHEEL_LOCK } else { 0.0 };
+            wheel.transform(ctx).write().unwrap().update(
+                Mat4::from_translation(offset)
+                    * Mat4::from_rotation_y(yaw)
+                    * Mat4::from_rotation_x(pose.wheel_spin),
+            );
+        }
+    }
+
+    fn pose(&self) -> Pose {
+        Pose {
+            position: self.vehicle.position,
+            heading: self.vehicle.heading,
+            wheel_spin: self.wheel_spin,
+        }
+    }
+}
@@ -0,0 +1,498 @@
+//! Collision against the static track geometry.
+//!
+//! rivik doesn't do physics, so the demo parses the track meshes itself. Triangles are
+//! bucketed into a uniform grid on the ground plane for the broad phase. The car is treated
+//! as a box on the ground plane that gets pushed out of walls, and rays cast down from each
+//! wheel decide whether the car is still on the paved surface.
+
+use std::{fs, io, path::PathBuf};
+
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{math, vehicle::Vehicle};
+
+/// Size of a broad-phase grid cell in world units.
+const CELL_SIZE: f32 = 4.0;
+/// Triangles steeper than this (by the Y component of their normal) count as walls.
+const WALL_MAX_NORMAL_Y: f32 = 0.5;
+/// Triangles flatter than this count as driveable surface.
+const FLOOR_MIN_NORMAL_Y: f32 = 0.9;
+/// Walls have to reach at least this high to stop the car, so curbs can be driven over.
+const WALL_MIN_HEIGHT: f32 = 0.3;
+/// Track surface below this height is grass and gravel rather than tarmac.
+const ROAD_MIN_HEIGHT: f32 = 0.05;
+/// Height of the car roof. Overhead geometry starting above it is ignored.
+const BODY_TOP: f32 = 1.35;
+/// Fraction of the velocity into a wall that is kept, bounced back out.
+const RESTITUTION: f32 = 0.2;
+
+#[derive(Clone, Copy)]
+pub struct Triangle {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+}
+
+impl Triangle {
+    pub fn normal(&self) -> Vec3 {
+        (self.b - self.a).cross(self.c - self.a).normalize_or_zero()
+    }
+
+    fn min(&self) -> Vec3 {
+        self.a.min(self.b).min(self.c)
+    }
+
+    fn max(&self) -> Vec3 {
+        self.a.max(self.b).max(self.c)
+    }
+
+    /// Distance along `dir` at which a ray from `origin` hits the triangle (Möller–Trumbore).
+    pub fn raycast(&self, origin: Vec3, dir: Vec3) -> Option<f32> {
+        let e1 = self.b - self.a;
+        let e2 = self.c - self.a;
+        let p = dir.cross(e2);
+        let det = e1.dot(p);
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv = 1.0 / det;
+        let t = origin - self.a;
+        let u = t.dot(p) * inv;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = t.cross(e1);
+        let v = dir.dot(q) * inv;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = e2.dot(q) * inv;
+        (distance >= 0.0).then_some(distance)
+    }
+}
+
+/// Reads the triangles of a Wavefront OBJ file. Only positions and faces are used, polygons
+/// are fan triangulated.
+pub fn parse_obj(src: &str) -> io::Result<Vec<Triangle>> {
+    let invalid =
+        |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad OBJ line: {line}"));
+
+    let mut positions = Vec::new();
+    let mut triangles = Vec::new();
+    for line in src.lines() {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("v") => {
+                let mut coord = || -> io::Result<f32> {
+                    parts
+                        .next()
+                        .and_then(|p| p.parse().ok())
+                        .ok_or_else(|| invalid(line))
+                };
+                positions.push(Vec3::new(coord()?, coord()?, coord()?));
+            }
+            Some("f") => {
+                let face = parts
+                    .map(|vertex| {
+                        let index: isize = vertex
+                            .split('/')
+                            .next()
+                            .and_then(|i| i.parse().ok())
+                            .ok_or_else(|| invalid(line))?;
+                        // OBJ indices are 1-based, negative ones count back from the end
+                        let index = if index < 0 {
+                            positions.len() as isize + index
+                        } else {
+                            index - 1
+                        };
+                        positions
+                            .get(index as usize)
+                            .copied()
+                            .ok_or_else(|| invalid(line))
+                    })
+                    .collect::<io::Result<Vec<_>>>()?;
+                for i in 1..face.len().saturating_sub(1) {
+                    triangles.push(Triangle {
+                        a: face[0],
+                        b: face[i],
+                        c: face[i + 1],
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+/// Triangles bucketed by their footprint on the ground plane.
+pub struct CollisionMesh {
+    triangles: Vec<Triangle>,
+    origin: Vec2,
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<u32>>,
+}
+
+impl CollisionMesh {
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        let (min, max) = triangles.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), tri| (min.min(ground(tri.min())), max.max(ground(tri.max()))),
+        );
+        let (min, max) = if triangles.is_empty() {
+            (Vec2::ZERO, Vec2::ZERO)
+        } else {
+            (min, max)
+        };
+
+        let width = ((max.x - min.x) / CELL_SIZE) as usize + 1;
+        let height = ((max.y - min.y) / CELL_SIZE) as usize + 1;
+        let mut mesh = Self {
+            triangles,
+            origin: min,
+            width,
+            height,
+            cells: vec![Vec::new(); width * height],
+        };
+
+        for (i, tri) in mesh.triangles.iter().enumerate() {
+            let (x0, y0) = mesh.cell(ground(tri.min()));
+            let (x1, y1) = mesh.cell(ground(tri.max()));
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    mesh.cells[y * width + x].push(i as u32);
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Triangles whose grid cells overlap the rectangle between `min` and `max` on the
+    /// ground plane.
+    pub fn query(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = &Triangle> {
+        let (x0, y0) = self.cell(min);
+        let (x1, y1) = self.cell(max);
+        let mut hits: Vec<u32> = (y0..=y1)
+            .flat_map(|y| (x0..=x1).map(move |x| y * self.width + x))
+            .flat_map(|cell| self.cells[cell].iter().copied())
+            .collect();
+        hits.sort_unstable();
+        hits.dedup();
+        hits.into_iter().map(move |i| &self.triangles[i as usize])
+    }
+
+    /// Closest hit of a ray against the mesh, ignoring anything further than `max`.
+    pub fn raycast(&self, origin: Vec3, dir: Vec3, max: f32) -> Option<f32> {
+        let end = origin + dir * max;
+        let min_xz = ground(origin.min(end));
+        let max_xz = ground(origin.max(end));
+        self.query(min_xz, max_xz)
+            .filter_map(|tri| tri.raycast(origin, dir))
+            .filter(|&t| t <= max)
+            .min_by(f32::total_cmp)
+    }
+
+    fn cell(&self, p: Vec2) -> (usize, usize) {
+        let rel = (p - self.origin) / CELL_SIZE;
+        let x = (rel.x.max(0.0) as usize).min(self.width - 1);
+        let y = (rel.y.max(0.0) as usize).min(self.height - 1);
+        (x, y)
+    }
+}
+
+/// A mesh that makes up part of the track collision.
+pub struct Source {
+    pub path: PathBuf,
+    /// Placement of the mesh in the world.
+    pub transform: Mat4,
+    /// Whether raised flat faces count as road. Steep faces are walls either way.
+    pub road: bool,
+}
+
+/// What the car ran into this step.
+pub struct Contact {
+    /// Speed the car was travelling into the wall before the hit.
+    pub impact_speed: f32,
+}
+
+/// Static collision for the whole track.
+pub struct TrackCollision {
+    walls: CollisionMesh,
+    road: CollisionMesh,
+}
+
+impl TrackCollision {
+    /// Builds collision from the track mesh and the props placed along it.
+    pub fn load(sources: &[Source]) -> io::Result<Self> {
+        let mut walls = Vec::new();
+        let mut road = Vec::new();
+
+        for source in sources {
+            for tri in parse_obj(&fs::read_to_string(&source.path)?)? {
+                let tri = Triangle {
+                    a: source.transform.transform_point3(tri.a),
+                    b: source.transform.transform_point3(tri.b),
+                    c: source.transform.transform_point3(tri.c),
+                };
+                let normal_y = tri.normal().y.abs();
+                if normal_y < WALL_MAX_NORMAL_Y
+                    && tri.max().y > WALL_MIN_HEIGHT
+                    && tri.min().y < BODY_TOP
+                {
+                    walls.push(tri);
+                } else if source.road
+                    && normal_y > FLOOR_MIN_NORMAL_Y
+                    && tri.min().y > ROAD_MIN_HEIGHT
+                {
+                    road.push(tri);
+                }
+            }
+        }
+
+        Ok(Self {
+            walls: CollisionMesh::new(walls),
+            road: CollisionMesh::new(road),
+        })
+    }
+
+    /// Pushes the car out of any walls it overlaps and removes the velocity driving it into
+    /// them.
+    pub fn resolve(&self, vehicle: &mut Vehicle) -> Option<Contact> {
+        let mut contact: Option<Contact> = None;
+
+        let center = ground(vehicle.position);
+        let axes = [
+            ground(math::left(vehicle.heading)),
+            ground(math::forward(vehicle.heading)),
+        ];
+        let chassis = vehicle.chassis;
+        let extents = [chassis.half_width, chassis.half_length];
+        // a corner of the box turned at any angle stays within its half diagonal
+        let reach = Vec2::splat(Vec2::new(chassis.half_width, chassis.half_length).length());
+
+        for tri in self.walls.query(center - reach, center + reach) {
+            // earlier walls may already have moved the car
+            let center = ground(vehicle.position);
+            let (a, b) = wall_segment(tri);
+            let Some(push) = box_segment_push(center, axes, extents, a, b) else {
+                continue;
+            };
+
+            vehicle.position += Vec3::new(push.x, 0.0, push.y);
+
+            let normal = Vec3::new(push.x, 0.0, push.y).normalize_or_zero();
+            let into = vehicle.velocity.dot(normal);
+            if into < 0.0 {
+                vehicle.velocity -= normal * into * (1.0 + RESTITUTION);
+                if contact.as_ref().is_none_or(|c| -into > c.impact_speed) {
+                    contact = Some(Contact {
+                        impact_speed: -into,
+                    });
+                }
+            }
+        }
+        contact
+    }
+
+    /// Whether any wheel is over tarmac.
+    pub fn on_road(&self, vehicle: &Vehicle) -> bool {
+        const PROBE_HEIGHT: f32 = 1.0;
+        const PROBE_DEPTH: f32 = 2.0;
+
+        let body = vehicle.transform();
+        vehicle.chassis.wheels.iter().any(|&wheel| {
+            let mut origin = body.transform_point3(wheel);
+            origin.y += PROBE_HEIGHT;
+            self.road
+                .raycast(origin, Vec3::NEG_Y, PROBE_DEPTH)
+                .is_some()
+        })
+    }
+}
+
+fn ground(p: Vec3) -> Vec2 {
+    Vec2::new(p.x, p.z)
+}
+
+/// A wall triangle seen from above collapses to (roughly) a line. Use its longest edge.
+fn wall_segment(tri: &Triangle) -> (Vec2, Vec2) {
+    let (a, b, c) = (ground(tri.a), ground(tri.b), ground(tri.c));
+    [(a, b), (b, c), (c, a)]
+        .into_iter()
+        .max_by(|(a0, a1), (b0, b1)| {
+            a0.distance_squared(*a1)
+                .total_cmp(&b0.distance_squared(*b1))
+        })
+        .unwrap()
+}
+
+/// Separating axis test between an oriented box and a line segment on the ground plane.
+///
+/// Returns the smallest translation that moves the box out of the segment.
+fn box_segment_push(
+    center: Vec2,
+    axes: [Vec2; 2],
+    extents: [f32; 2],
+    a: Vec2,
+    b: Vec2,
+) -> Option<Vec2> {
+    let edge = b - a;
+    if edge.length_squared() < f32::EPSILON {
+        return None;
+    }
+
+    let mut best: Option<(f32, Vec2)> = None;
+    for axis in [axes[0], axes[1], edge.perp().normalize()] {
+        let box_center = center.dot(axis);
+        let box_radius =
+            extents[0] * axes[0].dot(axis).abs() + extents[1] * axes[1].dot(axis).abs();
+        let (seg_min, seg_max) = {
+            let (pa, pb) = (a.dot(axis), b.dot(axis));
+            (pa.min(pb), pa.max(pb))
+        };
+
+        // push whichever way is shorter
+        let push_pos = seg_max - (box_center - box_radius);
+        let push_neg = (box_center + box_radius) - seg_min;
+        if push_pos <= 0.0 || push_neg <= 0.0 {
+            return None;
+        }
+        let push = if push_pos < push_neg {
+            axis * push_pos
+        } else {
+            -axis * push_neg
+        };
+        if best.is_none_or(|(depth, _)| push_pos.min(push_neg) < depth) {
+            best = Some((push_pos.min(push_neg), push));
+        }
+    }
+    best.map(|(_, push)| push)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vehicle::Chassis;
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    #[test]
+    fn parse_obj_fan_triangulates_faces() {
+        let src = "\
+# a unit quad
+v 0 0 0
+v 1 0 0
+v 1 0 1
+v 0 0 1
+vt 0 0
+vn 0 1 0
+f 1/1/1 2/1/1 3/1/1 4/1/1
+";
+        let triangles = parse_obj(src).unwrap();
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[0].a, Vec3::ZERO);
+        assert_eq!(triangles[0].c, Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(triangles[1].b, Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(triangles[1].c, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn parse_obj_counts_negative_indices_from_the_end() {
+        let src = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n";
+        let triangles = parse_obj(src).unwrap();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].a, Vec3::ZERO);
+        assert_eq!(triangles[0].c, Vec3::Y);
+    }
+
+    #[test]
+    fn parse_obj_rejects_bad_lines() {
+        assert!(parse_obj("v 0 0\n").is_err());
+        assert!(parse_obj("v 0 0 x\n").is_err());
+        assert!(parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+        assert!(parse_obj("v 0 0 0\nf 0 1 1\n").is_err());
+    }
+
+    fn floor() -> Triangle {
+        Triangle {
+            a: Vec3::new(0.0, 0.0, 0.0),
+            b: Vec3::new(0.0, 0.0, 4.0),
+            c: Vec3::new(4.0, 0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn raycast_hits_triangle_below() {
+        let hit = floor().raycast(Vec3::new(1.0, 2.0, 1.0), Vec3::NEG_Y);
+        assert!(hit.is_some_and(|t| (t - 2.0).abs() < 1e-5));
+    }
+
+    #[test]
+    fn raycast_misses() {
+        let tri = floor();
+        // beside the triangle
+        assert_eq!(tri.raycast(Vec3::new(3.0, 2.0, 3.0), Vec3::NEG_Y), None);
+        // pointing away from it
+        assert_eq!(tri.raycast(Vec3::new(1.0, 2.0, 1.0), Vec3::Y), None);
+        // parallel to it
+        assert_eq!(tri.raycast(Vec3::new(1.0, 2.0, 1.0), Vec3::X), None);
+    }
+
+    /// A 2 by 4 box at the origin, aligned with the axes.
+    fn push(a: Vec2, b: Vec2) -> Option<Vec2> {
+        box_segment_push(Vec2::ZERO, [Vec2::X, Vec2::Y], [1.0, 2.0], a, b)
+    }
+
+    #[test]
+    fn box_is_pushed_the_short_way_out() {
+        let from_right = push(Vec2::new(0.5, -5.0), Vec2::new(0.5, 5.0)).unwrap();
+        assert!(close(from_right, Vec2::new(-0.5, 0.0)));
+
+        let from_behind = push(Vec2::new(-5.0, -1.5), Vec2::new(5.0, -1.5)).unwrap();
+        assert!(close(from_behind, Vec2::new(0.0, 0.5)));
+    }
+
+    #[test]
+    fn angled_corner_reaching_into_the_next_cell_is_pushed_out() {
+        let chassis = Chassis {
+            half_width: 0.97,
+            half_length: 1.97,
+            wheels: [Vec3::ZERO; 4],
+            wheel_radius: 0.31,
+        };
+        // turned so the box reaches furthest along +X, past its half length
+        let heading = (chassis.half_length / chassis.half_width).atan();
+        let mut vehicle = Vehicle::new(chassis, Vec3::ZERO, heading);
+        // a wall just past the cell boundary at x = 2, with a far off triangle putting the
+        // grid origin at x = -10
+        let wall = Triangle {
+            a: Vec3::new(2.1, 0.0, -4.0),
+            b: Vec3::new(2.1, 0.0, 4.0),
+            c: Vec3::new(2.1, 2.0, 0.0),
+        };
+        let far = Triangle {
+            a: Vec3::new(-10.0, 0.0, -4.0),
+            b: Vec3::new(-10.0, 0.0, -3.0),
+            c: Vec3::new(-10.0, 2.0, -4.0),
+        };
+        let collision = TrackCollision {
+            walls: CollisionMesh::new(vec![wall, far]),
+            road: CollisionMesh::new(Vec::new()),
+        };
+
+        collision.resolve(&mut vehicle);
+        assert!(vehicle.position.x < -0.05, "{}", vehicle.position.x);
+    }
+
+    #[test]
+    fn box_clear_of_segment_is_not_pushed() {
+        assert_eq!(push(Vec2::new(2.0, -5.0), Vec2::new(2.0, 5.0)), None);
+        // only the diagonal's own axis separates these
+        assert_eq!(push(Vec2::new(0.8, 2.8), Vec2::new(1.8, 1.8)), None);
+        // a degenerate segment has no direction to push along
+        assert_eq!(push(Vec2::ZERO, Vec2::ZERO), None);
+    }
+}
@@ -0,0 +1,331 @@
+//! Driving input.
+//!
+//! Keyboard and gamepad input are normalized into the same set of axes so the car physics
+//! only ever sees analog values:
+//!
+//! - `steer` in `[-1.0, 1.0]`, negative is left.
+//! - `throttle` and `brake` in `[0.0, 1.0]`.
+//! - `handbrake` as a plain button.
+//!
+//! Digital keys synthesize full deflection (A/D => -1/+1 with the default bindings, see
+//! [`crate::bindings`]). Sticks and triggers go through a dead zone and a response curve
+//! first, both set in the bindings. Gamepad support uses gilrs and is behind the `gamepad`
+//! feature so headless builds don't need the backend.
+//!
+//! With a key and a stick on the same action, steering adds both up and clamps the sum to
+//! full lock, while throttle and brake take whichever is further down.
+//!
+//! Besides the driving axes, `Input` keeps track of key presses and mouse drags for things
+//! like the camera controls.
+
+use std::collections::HashSet;
+
+use glam::Vec2;
+use rivik::winit::event::{ElementState, MouseButton, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    bindings::{Bindings, Keys},
+    vehicle::Controls,
+};
+
+pub struct Input {
+    bindings: Bindings,
+    held: HashSet<VirtualKeyCode>,
+    /// Keys that went down in the current frame.
+    pressed: HashSet<VirtualKeyCode>,
+    /// Keys that went down since the last [`Input::poll`].
+    pending_presses: HashSet<VirtualKeyCode>,
+    cursor: Option<Vec2>,
+    /// Whether the mouse button for dragging is held.
+    dragging: bool,
+    /// Cursor movement while dragging in the current frame.
+    drag: Vec2,
+    /// Cursor movement while dragging since the last [`Input::poll`].
+    pending_drag: Vec2,
+    gamepad: gamepad::Gamepad,
+}
+
+impl Input {
+    pub fn new(bindings: Bindings) -> Self {
+        Self {
+            bindings,
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+            pending_presses: HashSet::new(),
+            cursor: None,
+            dragging: false,
+            drag: Vec2::ZERO,
+            pending_drag: Vec2::ZERO,
+            gamepad: gamepad::Gamepad::new(),
+        }
+    }
+
+    /// Tracks held keys and the mouse. Forward every event from `App::on_event`.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                let Some(key) = input.virtual_keycode else {
+                    return;
+                };
+                match input.state {
+                    // held keys repeat, only the first press counts
+                    ElementState::Pressed => {
+                        if self.held.insert(key) {
+                            self.pending_presses.insert(key);
+                        }
+                    }
+                    ElementState::Released => {
+                        self.held.remove(&key);
+                    }
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = Vec2::new(position.x as f32, position.y as f32);
+                if let Some(last) = self.cursor.replace(position) {
+                    if self.dragging {
+                        self.pending_drag += position - last;
+                    }
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Right,
+                ..
+            } => self.dragging = *state == ElementState::Pressed,
+            _ => {}
+        }
+    }
+
+    /// Starts a new frame of input and pulls pending gamepad events. Call once per frame
+    /// before reading anything.
+    pub fn poll(&mut self) {
+        self.pressed = std::mem::take(&mut self.pending_presses);
+        self.drag = std::mem::take(&mut self.pending_drag);
+        self.gamepad.poll();
+    }
+
+    pub fn is_held(&self, key: VirtualKeyCode) -> bool {
+        self.held.contains(&key)
+    }
+
+    /// Whether `key` went down this frame.
+    pub fn was_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    /// Pixels the cursor moved this frame with the right mouse button held.
+    pub fn drag(&self) -> Vec2 {
+        self.drag
+    }
+
+    /// Snapshot of every driving axis.
+    pub fn controls(&self) -> Controls {
+        Controls {
+            steer: self.steer(),
+            throttle: self.throttle(),
+            brake: self.brake(),
+            handbrake: self.handbrake(),
+        }
+    }
+
+    /// Driving axes of the second player, who only has a keyboard.
+    pub fn player_two(&self) -> Controls {
+        let keys = &self.bindings.player_two;
+        Controls {
+            steer: self.keys(&keys.steer_right) - self.keys(&keys.steer_left),
+            throttle: self.keys(&keys.throttle),
+            brake: self.keys(&keys.brake),
+            handbrake: self.keys(&keys.handbrake) > 0.0,
+        }
+    }
+
+    pub fn steer(&self) -> f32 {
+        let keys = self.keyboard();
+        let keys = self.keys(&keys.steer_right) - self.keys(&keys.steer_left);
+        (keys + self.analog(self.gamepad.steer())).clamp(-1.0, 1.0)
+    }
+
+    pub fn throttle(&self) -> f32 {
+        let keys = self.keys(&self.keyboard().throttle);
+        keys.max(self.analog(self.gamepad.throttle()))
+    }
+
+    pub fn brake(&self) -> f32 {
+        let keys = self.keys(&self.keyboard().brake);
+        keys.max(self.analog(self.gamepad.brake()))
+    }
+
+    pub fn handbrake(&self) -> bool {
+        self.keys(&self.keyboard().handbrake) > 0.0 || self.gamepad.handbrake()
+    }
+
+    fn keyboard(&self) -> &Keys {
+        &self.bindings.keyboard
+    }
+
+    /// Full deflection if any of `keys` is held.
+    fn keys(&self, keys: &[VirtualKeyCode]) -> f32 {
+        if keys.iter().any(|key| self.held.contains(key)) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Applies the dead zone to a raw stick or trigger value, rescaling what's left so full
+    /// deflection still reaches 1, then shapes it with the response curve.
+    fn analog(&self, value: f32) -> f32 {
+        let zone = self.bindings.dead_zone;
+        if value.abs() <= zone {
+            return 0.0;
+        }
+        let value = value.signum() * ((value.abs() - zone) / (1.0 - zone)).min(1.0);
+        self.bindings.curve.apply(value)
+    }
+}
+
+#[cfg(feature = "gamepad")]
+mod gamepad {
+    use gilrs::{Axis, Button, GamepadId, Gilrs};
+
+    /// The most recently used controller.
+    pub struct Gamepad {
+        gilrs: Option<Gilrs>,
+        active: Option<GamepadId>,
+    }
+
+    impl Gamepad {
+        pub fn new() -> Self {
+            let gilrs = match Gilrs::new() {
+                Ok(gilrs) => Some(gilrs),
+                Err(e) => {
+                    tracing::warn!("Gamepad support unavailable: {e}");
+                    None
+                }
+            };
+            Self {
+                gilrs,
+                active: None,
+            }
+        }
+
+        pub fn poll(&mut self) {
+            let Some(gilrs) = &mut self.gilrs else {
+                return;
+            };
+            while let Some(event) = gilrs.next_event() {
+                self.active = Some(event.id);
+            }
+        }
+
+        pub fn steer(&self) -> f32 {
+            self.with_active(|pad| pad.value(Axis::LeftStickX))
+        }
+
+        pub fn throttle(&self) -> f32 {
+            self.with_active(|pad| {
+                pad.button_data(Button::RightTrigger2)
+                    .map_or(0.0, |b| b.value())
+            })
+        }
+
+        pub fn brake(&self) -> f32 {
+            self.with_active(|pad| {
+                pad.button_data(Button::LeftTrigger2)
+                    .map_or(0.0, |b| b.value())
+            })
+        }
+
+        pub fn handbrake(&self) -> bool {
+            match (&self.gilrs, self.active) {
+                (Some(gilrs), Some(id)) => gilrs.gamepad(id).is_pressed(Button::South),
+                _ => false,
+            }
+        }
+
+        fn with_active(&self, f: impl FnOnce(gilrs::Gamepad<'_>) -> f32) -> f32 {
+            match (&self.gilrs, self.active) {
+                (Some(gilrs), Some(id)) => f(gilrs.gamepad(id)),
+                _ => 0.0,
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "gamepad"))]
+mod gamepad {
+    /// Stand-in used when the demo is built without gamepad support.
+    pub struct Gamepad;
+
+    impl Gamepad {
+        pub fn new() -> Self {
+            Self
+        }
+
+        pub fn poll(&mut self) {}
+
+        pub fn steer(&self) -> f32 {
+            0.0
+        }
+
+        pub fn throttle(&self) -> f32 {
+            0.0
+        }
+
+        pub fn brake(&self) -> f32 {
+            0.0
+        }
+
+        pub fn handbrake(&self) -> bool {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::bindings::Curve;
+
+    fn input(dead_zone: f32, curve: Curve) -> Input {
+        let mut bindings = Bindings::load(Path::new("no-such-controls.toml"));
+        bindings.dead_zone = dead_zone;
+        bindings.curve = curve;
+        Input::new(bindings)
+    }
+
+    #[test]
+    fn analog_ignores_the_dead_zone() {
+        let input = input(0.2, Curve::Linear);
+        assert_eq!(input.analog(0.0), 0.0);
+        assert_eq!(input.analog(0.15), 0.0);
+        assert_eq!(input.analog(-0.2), 0.0);
+    }
+
+    #[test]
+    fn analog_is_continuous_at_the_dead_zone_edge() {
+        for curve in [Curve::Linear, Curve::Squared, Curve::Exponent(1.5)] {
+            let input = input(0.2, curve);
+            assert!(input.analog(0.2 + 1e-4).abs() < 1e-3, "{curve:?}");
+            assert!(input.analog(-0.2 - 1e-4).abs() < 1e-3, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn analog_reaches_full_deflection() {
+        for curve in [Curve::Linear, Curve::Squared, Curve::Exponent(0.5)] {
+            let input = input(0.2, curve);
+            assert!((input.analog(1.0) - 1.0).abs() < 1e-6, "{curve:?}");
+            assert!((input.analog(-1.0) + 1.0).abs() < 1e-6, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn analog_rescales_past_the_dead_zone() {
+        let input = input(0.2, Curve::Linear);
+        assert!((input.analog(0.6) - 0.5).abs() < 1e-6);
+        assert!((input.analog(-0.6) + 0.5).abs() < 1e-6);
+    }
+}
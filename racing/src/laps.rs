@@ -0,0 +1,350 @@
+//! Checkpoints and lap timing.
+//!
+//! The track is split up by trigger volumes that have to be driven through in order. The first
+//! checkpoint is the start/finish line: crossing it starts the clock, and crossing it again
+//! after every other checkpoint completes a lap. Cutting across the infield past a checkpoint
+//! doesn't count.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glam::Vec3;
+use rivik::egui;
+
+/// Axis aligned trigger volume.
+pub struct Checkpoint {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+}
+
+impl Checkpoint {
+    pub fn new(center: Vec3, half_extents: Vec3) -> Self {
+        Self {
+            center,
+            half_extents,
+        }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        let d = (point - self.center).abs();
+        d.x <= self.half_extents.x && d.y <= self.half_extents.y && d.z <= self.half_extents.z
+    }
+}
+
+pub struct LapTimer {
+    checkpoints: Vec<Checkpoint>,
+    /// Index of the checkpoint the car has to reach next, `None` until the car first crosses
+    /// the start line.
+    next: Option<usize>,
+    /// Every lap completed since the last reset.
+    times: Vec<f32>,
+    current: f32,
+    best: Option<f32>,
+    record: Option<PathBuf>,
+}
+
+impl LapTimer {
+    /// Builds a timer for the given checkpoints, the first one being the start/finish line.
+    /// There has to be at least one more, or a lap would end the moment it starts.
+    ///
+    /// The best lap is read from `record` if it exists and written back whenever it is beaten.
+    /// Without a record file the best lap only lasts as long as the timer.
+    pub fn new(checkpoints: Vec<Checkpoint>, record: Option<&Path>) -> Self {
+        assert!(
+            checkpoints.len() >= 2,
+            "a lap needs a checkpoint past the finish line"
+        );
+        Self {
+            checkpoints,
+            next: None,
+            times: Vec::new(),
+            current: 0.0,
+            best: record.and_then(load_best),
+            record: record.map(Path::to_owned),
+        }
+    }
+
+    /// Advances the clock by `dt` seconds and checks the car against the next checkpoint.
+    ///
+    /// Returns the lap time when a lap was completed this step.
+    pub fn update(&mut self, position: Vec3, dt: f32) -> Option<f32> {
+        let Some(next) = self.next else {
+            if self.checkpoints[0].contains(position) {
+                self.start_lap();
+            }
+            return None;
+        };
+
+        self.current += dt;
+        if !self.checkpoints[next].contains(position) {
+            return None;
+        }
+        if next != 0 {
+            self.next = Some((next + 1) % self.checkpoints.len());
+            return None;
+        }
+
+        let time = self.current;
+        self.times.push(time);
+        if self.best.is_none_or(|best| time < best) {
+            self.best = Some(time);
+            if let Some(record) = &self.record {
+                save_best(record, time);
+            }
+        }
+        self.start_lap();
+        Some(time)
+    }
+
+    /// Time into the lap in progress, `None` before the car first crosses the start line.
+    pub fn current(&self) -> Option<f32> {
+        self.next.map(|_| self.current)
+    }
+
+    pub fn best(&self) -> Option<f32> {
+        self.best
+    }
+
+    /// Number of laps completed since the last reset.
+    pub fn completed(&self) -> usize {
+        self.times.len()
+    }
+
+    /// Starts over for a new race, waiting for the car to cross the start line again. The
+    /// best lap is kept.
+    pub fn reset(&mut self) {
+        self.next = None;
+        self.times.clear();
+        self.current = 0.0;
+    }
+
+    /// Draws the race position, lap counter out of `laps` and times in the top right corner.
+    ///
+    /// In two-player races, `player` is the index of the player the timer belongs to. The
+    /// second player's times go in the top left corner.
+    pub fn ui(
+        &self,
+        ctx: &egui::Context,
+        player: Option<usize>,
+        position: usize,
+        cars: usize,
+        laps: usize,
+    ) {
+        let (anchor, offset) = if player == Some(1) {
+            (egui::Align2::LEFT_TOP, [10.0, 10.0])
+        } else {
+            (egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+        };
+        let title = player.map_or("Lap Times".to_owned(), |i| format!("Lap Times {i}"));
+        let name = player.map_or(String::new(), |i| format!("P{} ", i + 1));
+        egui::Window::new(title)
+            .anchor(anchor, offset)
+            .title_bar(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.heading(format!("{name}{} / {cars}", ordinal(position)));
+                ui.label(format!("Lap {} / {laps}", (self.completed() + 1).min(laps)));
+                ui.monospace(format!("Time {}", format_time(self.current())));
+                ui.monospace(format!("Last {}", format_time(self.times.last().copied())));
+                ui.monospace(format!("Best {}", format_time(self.best)));
+            });
+    }
+
+    /// Lists the time of every completed lap with the race total and the fastest lap of the
+    /// race.
+    pub fn results(&self, ui: &mut egui::Ui) {
+        for (i, &time) in self.times.iter().enumerate() {
+            ui.monospace(format!("Lap {:<2} {}", i + 1, format_time(Some(time))));
+        }
+        ui.separator();
+        let total = self.times.iter().sum();
+        let fastest = self.times.iter().copied().reduce(f32::min);
+        ui.monospace(format!("Total  {}", format_time(Some(total))));
+        ui.monospace(format!("Best   {}", format_time(fastest)));
+    }
+
+    fn start_lap(&mut self) {
+        self.current = 0.0;
+        self.next = Some(1);
+    }
+}
+
+/// Formats seconds as `m:ss.mmm`, or dashes if there is no time yet.
+fn format_time(seconds: Option<f32>) -> String {
+    let Some(seconds) = seconds else {
+        return "-:--.---".to_owned();
+    };
+    let millis = (seconds * 1000.0).round() as u32;
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// `1` => `1st`, `2` => `2nd`, ...
+pub fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+fn load_best(path: &Path) -> Option<f32> {
+    let best = fs::read_to_string(path).ok()?;
+    match best.trim().parse() {
+        Ok(best) => Some(best),
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable best lap in {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+fn save_best(path: &Path, time: f32) {
+    if let Err(e) = fs::write(path, format!("{time}\n")) {
+        tracing::warn!("Failed to save best lap to {}: {e}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 0.1;
+    const FINISH: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+    const FIRST: Vec3 = Vec3::new(0.0, 0.0, 10.0);
+    const SECOND: Vec3 = Vec3::new(0.0, 0.0, 20.0);
+    /// Between the checkpoints, inside none of them.
+    const TRACK: Vec3 = Vec3::new(0.0, 0.0, 5.0);
+
+    /// A file in the temp directory that is removed again when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let name = format!("racing-{}-{name}.txt", std::process::id());
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn timer(record: Option<&Path>) -> LapTimer {
+        let checkpoint = |center| Checkpoint::new(center, Vec3::new(3.0, 3.0, 1.0));
+        let checkpoints = vec![checkpoint(FINISH), checkpoint(FIRST), checkpoint(SECOND)];
+        LapTimer::new(checkpoints, record)
+    }
+
+    /// Drives through `path` one step each, returning the last lap time completed.
+    fn drive(timer: &mut LapTimer, path: &[Vec3]) -> Option<f32> {
+        path.iter()
+            .filter_map(|&position| timer.update(position, DT))
+            .last()
+    }
+
+    #[test]
+    fn clock_starts_at_the_finish_line() {
+        let mut timer = timer(None);
+        assert_eq!(drive(&mut timer, &[TRACK, FIRST]), None);
+        assert_eq!(timer.current(), None);
+        drive(&mut timer, &[FINISH]);
+        assert_eq!(timer.current(), Some(0.0));
+    }
+
+    #[test]
+    fn lap_counts_after_every_checkpoint_in_order() {
+        let mut timer = timer(None);
+        let lap = drive(
+            &mut timer,
+            &[FINISH, TRACK, FIRST, TRACK, SECOND, TRACK, FINISH],
+        );
+        assert!(lap.is_some_and(|time| (time - 6.0 * DT).abs() < 1e-5));
+        assert_eq!(timer.completed(), 1);
+        assert_eq!(timer.current(), Some(0.0));
+    }
+
+    #[test]
+    fn skipping_a_checkpoint_does_not_count() {
+        let mut timer = timer(None);
+        assert_eq!(
+            drive(&mut timer, &[FINISH, TRACK, SECOND, TRACK, FINISH]),
+            None
+        );
+        // out of order doesn't count either
+        assert_eq!(drive(&mut timer, &[SECOND, FIRST, FINISH]), None);
+        assert_eq!(timer.completed(), 0);
+    }
+
+    #[test]
+    fn best_lap_only_improves() {
+        let mut timer = timer(None);
+        let slow = [FIRST, TRACK, TRACK, SECOND, FINISH];
+        let fast = [FIRST, SECOND, FINISH];
+        drive(&mut timer, &[FINISH]);
+
+        drive(&mut timer, &slow);
+        let best = timer.best().unwrap();
+        assert!((best - 5.0 * DT).abs() < 1e-5);
+        drive(&mut timer, &slow);
+        drive(&mut timer, &slow);
+        assert_eq!(timer.best(), Some(best));
+        drive(&mut timer, &fast);
+        assert!(timer.best().is_some_and(|time| time < best));
+        assert_eq!(timer.completed(), 4);
+    }
+
+    #[test]
+    fn best_lap_is_saved_and_loaded() {
+        let file = TempFile::new("best-lap");
+        let mut first = timer(Some(&file.0));
+        assert_eq!(first.best(), None);
+        drive(&mut first, &[FINISH, FIRST, SECOND, FINISH]);
+
+        let second = timer(Some(&file.0));
+        assert_eq!(second.best(), first.best());
+    }
+
+    #[test]
+    fn missing_or_garbage_best_lap_is_none() {
+        let file = TempFile::new("garbage-lap");
+        assert_eq!(load_best(&file.0), None);
+        fs::write(&file.0, "fast\n").unwrap();
+        assert_eq!(load_best(&file.0), None);
+        save_best(&file.0, 61.5);
+        assert_eq!(load_best(&file.0), Some(61.5));
+    }
+
+    #[test]
+    fn times_are_formatted_as_minutes() {
+        assert_eq!(format_time(None), "-:--.---");
+        assert_eq!(format_time(Some(0.0)), "0:00.000");
+        assert_eq!(format_time(Some(83.4567)), "1:23.457");
+    }
+
+    #[test]
+    fn ordinals() {
+        let ordinals: Vec<String> = [1, 2, 3, 4, 11, 12, 13, 21, 22, 23, 101, 111]
+            .into_iter()
+            .map(ordinal)
+            .collect();
+        assert_eq!(
+            ordinals,
+            [
+                "1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd", "23rd",
+                "101st", "111th"
+            ]
+        );
+    }
+}
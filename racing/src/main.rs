@@ -1,9 +1,11 @@
 use std::{
-    mem,
-    sync::{Arc, RwLock},
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    time::Instant,
 };
 
-use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+use glam::{Mat4, Vec3};
 use rivik::{
     assets::{
         formats::{img::ImageFormat, mesh::ObjMesh},
@@ -11,44 +13,236 @@ use rivik::{
     },
     egui,
     render::{
-        draw::{mesh, pixel_mesh, Mesh, PixelMesh, SkyMesh},
+        draw::{mesh, Mesh, SkyMesh},
         lights::{ambient::AmbientLight, sun::SunLight},
         load::{GpuMesh, GpuTexture},
         tracing::UiSubscriber,
-        Transform,
     },
-    scene::Node,
-    winit::event::{ElementState, VirtualKeyCode, WindowEvent},
-    Handle,
+    winit::event::WindowEvent,
 };
 use tracing::{dispatcher::set_global_default, Dispatch};
 use tracing_subscriber::{prelude::__tracing_subscriber_SubscriberExt, Registry};
 
-pub struct App {
-    car: Handle<Mesh>,
-    speed: f32,
-    rotation: f32,
-    positon: Vec4,
+mod audio;
+mod bench;
+mod bindings;
+mod camera;
+mod car;
+mod collision;
+mod input;
+mod laps;
+mod math;
+mod opponents;
+mod replay;
+mod route;
+mod state;
+mod time;
+mod track;
+mod vehicle;
+
+use audio::Audio;
+use bench::Bench;
+use bindings::Bindings;
+use camera::Camera;
+use car::{Car, CarMesh};
+use collision::{Source, TrackCollision};
+use input::Input;
+use laps::LapTimer;
+use opponents::Opponents;
+use replay::{Ghost, Recorder, Replay};
+use route::Route;
+use state::{PauseChoice, State, COUNTDOWN};
+use time::{FixedStep, FrameClock, TICK};
+use track::{CarModel, Collision, Spawn, Track};
+use vehicle::Controls;
 
-    cam_position: Vec3,
+pub struct App {
+    state: State,
+    track_name: String,
+    /// Laps in a race.
+    race_laps: usize,
+    /// Starting positions, the players' first.
+    grid: Vec<Spawn>,
+
+    /// Everyone racing from this machine. The first player has the gamepad, the menu's car
+    /// selector, the ghost and the engine sound.
+    players: Vec<Player>,
+    model: CarModel,
+    /// Player car meshes in other colors, parked out of sight until they are picked again.
+    garage: HashMap<&'static str, CarMesh>,
+    opponents: Opponents,
+    route: Route,
+    collision: TrackCollision,
+    recorder: Recorder,
+    ghost: Ghost,
+    /// Where the ghost of this track's best lap is saved, `None` while benchmarking.
+    ghost_file: Option<PathBuf>,
+
+    /// Frames every player's car at once. rivik draws a single view, so with more than one
+    /// player this is shown instead of their own cameras.
+    overview: Camera,
+    audio: Audio,
+
+    clock: FrameClock,
+    step: FixedStep,
+    input: Input,
+    /// Set with `--bench`, see [`bench`].
+    bench: Option<Bench>,
 
     color: &'static str,
     last_color: &'static str,
+}
 
-    // input flags
-    gas: bool,
-    brake: bool,
-    left: bool,
-    right: bool,
+/// A car driven by someone at the keyboard.
+struct Player {
+    car: Car,
+    laps: LapTimer,
+    /// Chase camera following this player's car, shown when they race alone.
+    camera: Camera,
 }
 
-impl rivik::App for App {
-    fn init(ctx: &mut rivik::Context) -> Self {
+impl App {
+    /// Lines every car up on the grid and starts the countdown.
+    fn start_race(&mut self) {
+        let (starts, grid) = self.grid.split_at(self.players.len());
+        for (player, slot) in self.players.iter_mut().zip(starts) {
+            player.car.reset(&self.route, slot);
+            player.laps.reset();
+        }
+        self.opponents.reset(&self.route, grid);
+        self.recorder = Recorder::default();
+        self.state = State::Countdown(COUNTDOWN);
+    }
+
+    /// Swaps the player's car for one in the color picked in the menu, if it changed.
+    ///
+    /// rivik can't remove meshes from the scene, so the old car is parked in the garage and
+    /// reused if its color is picked again. That way every color is loaded at most once, and
+    /// the scene never holds more than one car per color. rivik uploads meshes and textures
+    /// to the GPU as they load, so this happens on the main thread and the first pick of a
+    /// color may hitch for a frame.
+    fn repaint(&mut self, ctx: &mut rivik::Context) {
+        if self.color == self.last_color {
+            return;
+        }
+        let mesh = match self.garage.remove(self.color) {
+            Some(mesh) => mesh,
+            None => match load_car(ctx, &self.model, &car_texture(self.color)) {
+                Ok(mesh) => mesh,
+                Err(e) => {
+                    tracing::warn!("Failed to load the {} car: {e}", self.color);
+                    self.color = self.last_color;
+                    return;
+                }
+            },
+        };
+        let old = std::mem::replace(&mut self.players[0].car.mesh, mesh);
+        old.park(ctx);
+        self.garage.insert(self.last_color, old);
+        self.last_color = self.color;
+    }
+
+    /// Advances the race by one fixed [`TICK`]. Everything that affects gameplay happens
+    /// here, so it plays out the same at any frame rate.
+    fn tick(&mut self) {
+        for (i, player) in self.players.iter_mut().enumerate() {
+            let controls = if self.bench.is_some() {
+                opponents::follow_line(&player.car, &self.route, bench::PACE)
+            } else if i > 0 {
+                self.input.player_two()
+            } else if player.camera.flying() {
+                // the free camera borrows the first player's driving keys
+                Controls::default()
+            } else {
+                self.input.controls()
+            };
+            let contact = player
+                .car
+                .drive(&controls, TICK, &self.collision, &self.route);
+            if let (0, Some(contact)) = (i, contact) {
+                self.audio.impact(contact.impact_speed);
+            }
+        }
+        let leader = self
+            .players
+            .iter()
+            .map(|player| player.car.distance(&self.route))
+            .fold(f32::MIN, f32::max);
+        self.opponents
+            .update(TICK, &self.collision, &self.route, leader);
+
+        let split = self.players.len() > 1;
+        for (i, player) in self.players.iter_mut().enumerate() {
+            let Some(time) = player.laps.update(player.car.vehicle.position, TICK) else {
+                continue;
+            };
+            if split {
+                tracing::info!("P{} completed a lap in {time:.3}s", i + 1);
+            } else {
+                tracing::info!("Lap completed in {time:.3}s");
+            }
+            if i > 0 {
+                continue;
+            }
+            // only the first player races the ghost
+            let lap = self.recorder.finish(time, &player.car.vehicle);
+            if player.laps.best() == Some(time) {
+                if let Some(file) = &self.ghost_file {
+                    if let Err(e) = lap.save(file) {
+                        tracing::warn!("Failed to save ghost to {}: {e}", file.display());
+                    }
+                }
+                self.ghost.set_replay(lap);
+            }
+        }
+        let first = &self.players[0];
+        if let Some(time) = first.laps.current() {
+            self.recorder.record(time, &first.car.vehicle);
+        }
+    }
+
+    /// Whether any player has driven the full race distance. The race is over as soon as the
+    /// first player finishes, with everyone else placed by how far they got.
+    fn finished(&self) -> bool {
+        self.players
+            .iter()
+            .any(|player| player.laps.completed() >= self.race_laps)
+    }
+
+    /// Race position of player `i`, 1 for the lead, and the number of cars in the race.
+    fn placing(&self, i: usize) -> (usize, usize) {
+        let distance = self.players[i].car.distance(&self.route);
+        let cars = self.players.iter().map(|player| &player.car);
+        let distances: Vec<f32> = cars
+            .chain(self.opponents.cars())
+            .map(|car| car.distance(&self.route))
+            .collect();
+        let ahead = distances.iter().filter(|&&d| d > distance).count();
+        (ahead + 1, distances.len())
+    }
+
+    /// Loads the track and everything racing on it.
+    fn load(ctx: &mut rivik::Context) -> io::Result<Self> {
         //ctx.show_trace = true;
-        load_track(ctx);
+        let track_file = track_file()?;
+        let mut track = Track::load(&track_file)?;
+        tracing::info!("Loading {} from {}", track.name, track_file.display());
+        let model = CarModel::load(&asset_file(CAR_FILE)?)?;
+        let bench = flag("--bench").then(|| Bench::new(bench_out()));
+        let scale = if bench.is_some() {
+            scale()
+        } else {
+            if arg("--scale").is_some() {
+                tracing::warn!("Ignoring --scale without --bench");
+            }
+            0
+        };
+
+        load_scenery(ctx, &track)?;
+        bench::load_props(ctx, &track, scale)?;
         ctx.insert_light(SunLight::new(Vec3::ONE, Vec3::new(2.0, 1.0, 0.0)));
         ctx.insert_light(AmbientLight::new(0.05, 0.05, 0.1));
-        ctx.insert(load_sky("file:assets/sky.jpeg", ImageFormat::Jpeg));
+        ctx.insert(load_sky(&track.sky)?);
         ctx.camera = Mat4::look_at_rh(
             Vec3::new(00.0, 10.0, 20.0),
             Vec3::new(0.0, 0.0, 00.0),
@@ -56,230 +250,447 @@ impl rivik::App for App {
         );
         ctx.far = 10_000.0;
 
-        Self {
-            car: load_car(
-                ctx,
-                "file:assets/textures/CompactCar_Texture_Muscle_Red.png",
-            ),
-            speed: 0.0,
-            rotation: 0.0,
-            positon: Vec4::new(-6.8, 0.0, 17.0, 1.0),
-            cam_position: Vec3::ZERO,
-
-            color: "Neon",
-            last_color: "Neon",
-
-            brake: false,
-            gas: false,
-            left: false,
-            right: false,
+        // autopilot laps aren't records, and mustn't race whatever ghost is saved either
+        let records = bench.is_none();
+        let best_lap_file = records.then(|| record_file(&track_file, BEST_LAP_FILE));
+        let ghost_file = records.then(|| record_file(&track_file, GHOST_FILE));
+
+        let route = Route::new(&track.racing_line());
+        let mut players = player_count();
+        if players > track.spawns.len() {
+            tracing::warn!(
+                "{} only has room for {} players",
+                track.name,
+                track.spawns.len()
+            );
+            players = track.spawns.len();
         }
+        track.spawns.extend(bench::scale_grid(&route, scale));
+        let (starts, grid) = track.spawns.split_at(players);
+        let players = starts
+            .iter()
+            .zip(PLAYER_COLORS)
+            .enumerate()
+            .map(|(i, (slot, color))| {
+                Ok(Player {
+                    car: Car::new(
+                        load_car(ctx, &model, &car_texture(color))?,
+                        model.chassis(),
+                        &route,
+                        slot,
+                    ),
+                    // the saved best lap is the first player's, like the ghost
+                    laps: LapTimer::new(
+                        track.checkpoints(),
+                        best_lap_file.as_deref().filter(|_| i == 0),
+                    ),
+                    camera: Camera::new(),
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let opponents = Opponents::spawn(ctx, &model, &route, grid)?;
+        let ghost = Ghost::new(
+            load_car(ctx, &model, GHOST_TEXTURE)?,
+            ghost_file.as_deref().and_then(load_ghost),
+        );
+        let collision = load_collision(&track)?;
+
+        Ok(Self {
+            // benchmarks start racing right away
+            state: if bench.is_some() {
+                State::Racing
+            } else {
+                State::Menu
+            },
+            track_name: track.name,
+            race_laps: track.laps,
+            grid: track.spawns,
+
+            players,
+            model,
+            garage: HashMap::new(),
+            opponents,
+            route,
+            collision,
+            recorder: Recorder::default(),
+            ghost,
+            ghost_file,
+            overview: Camera::new(),
+            audio: Audio::new(),
+
+            clock: FrameClock::new(),
+            step: FixedStep::new(),
+            input: Input::new(Bindings::load(Path::new(CONTROLS_FILE))),
+            bench,
+
+            color: PLAYER_COLORS[0],
+            last_color: PLAYER_COLORS[0],
+        })
     }
 
-    // fn ui(&mut self, ctx: &egui::Context) {
-    //     egui::Window::new("Car Selector").show(ctx, |ui| {
-    //         egui::ComboBox::from_label("Color")
-    //             .selected_text(format!("{}", self.color))
-    //             .show_ui(ui, |ui| {
-    //                 ui.selectable_value(&mut self.color, "Black", "Black");
-    //                 ui.selectable_value(&mut self.color, "Blue", "Blue");
-    //                 ui.selectable_value(&mut self.color, "Brown", "Brown");
-    //                 ui.selectable_value(&mut self.color, "Gray", "Gray");
-    //                 ui.selectable_value(&mut self.color, "Green", "Green");
-    //                 ui.selectable_value(&mut self.color, "Muscle_Blue", "Muscle Blue");
-    //                 ui.selectable_value(&mut self.color, "Muscle_Orange", "Muscle Orange");
-    //                 ui.selectable_value(&mut self.color, "Muscle_Red", "Muscle Red");
-    //                 ui.selectable_value(&mut self.color, "Neon", "Neon");
-    //                 ui.selectable_value(&mut self.color, "Orange", "Orange");
-    //                 ui.selectable_value(&mut self.color, "Pink", "Pink");
-    //                 ui.selectable_value(&mut self.color, "Police", "Police");
-    //                 ui.selectable_value(&mut self.color, "Red", "Red");
-    //                 ui.selectable_value(&mut self.color, "Taxi", "Taxi");
-    //                 ui.selectable_value(&mut self.color, "White", "White");
-    //                 ui.selectable_value(&mut self.color, "Yellow", "Yellow");
-    //             });
-    //     });
-    // }
+    /// Lap times of every player.
+    fn hud(&self, ctx: &egui::Context) {
+        let split = self.players.len() > 1;
+        for (i, player) in self.players.iter().enumerate() {
+            let (position, cars) = self.placing(i);
+            player
+                .laps
+                .ui(ctx, split.then_some(i), position, cars, self.race_laps);
+        }
+    }
+}
+
+impl rivik::App for App {
+    fn init(ctx: &mut rivik::Context) -> Self {
+        match App::load(ctx) {
+            Ok(app) => app,
+            Err(e) => {
+                tracing::error!("Failed to load the race: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
 
     fn update(&mut self, ctx: &mut rivik::Context) {
-        // car control
-        const ACC: f32 = 0.05;
-        const DECEL: f32 = 0.07;
-        const STEER: f32 = 0.02;
-        const MAX: f32 = 0.23;
-        const RESIST: f32 = 0.02;
-
-        if self.gas {
-            self.speed += (MAX - self.speed) * ACC;
+        let started = Instant::now();
+        self.clock.tick();
+        if let Some(bench) = &mut self.bench {
+            if bench.frame(self.clock.delta()) {
+                bench.finish();
+                std::process::exit(0);
+            }
+        }
+        self.input.poll();
+        // benchmarks simulate the same time every frame, so every run does the same work
+        let dt = if self.bench.is_some() {
+            bench::FRAME
         } else {
-            // slow down due to friction;
-            self.speed -= self.speed * RESIST;
+            self.clock.delta_clamped()
+        };
+
+        // a timed run can't be paused
+        if self.bench.is_none() {
+            self.state = self.state.advance(dt, &self.input);
         }
-        if self.brake {
-            self.speed -= self.speed * DECEL;
+        if self.state == State::Racing {
+            for _ in 0..self.step.advance(dt) {
+                self.tick();
+                if self.finished() && self.bench.is_none() {
+                    tracing::info!("Race finished");
+                    self.state = State::Results;
+                    break;
+                }
+            }
         }
 
-        self.speed = self.speed.max(0.0);
+        self.repaint(ctx);
 
-        // // TODO: Steering needs to be reworked as a sideways force
-        let mut steer = 0.0;
-        if self.left {
-            steer += 1.0;
+        // draw everything between the last two ticks
+        let alpha = self.step.alpha();
+        for player in &self.players {
+            player.car.sync(ctx, alpha);
         }
-        if self.right {
-            steer -= 1.0;
+        self.opponents.sync(ctx, alpha);
+        let first = &self.players[0];
+        let lap_time = first
+            .laps
+            .current()
+            .map(|time| (time - (1.0 - alpha) * TICK).max(0.0));
+        self.ghost.update(ctx, lap_time);
+
+        // every player's camera keeps following their car, but rivik draws a single view, so
+        // with more than one player they share the overview instead
+        let cars: Vec<_> = self
+            .players
+            .iter()
+            .map(|player| player.car.interpolated(alpha))
+            .collect();
+        let views: Vec<_> = self
+            .players
+            .iter_mut()
+            .zip(&cars)
+            .map(|(player, car)| player.camera.update(&self.input, dt, &[*car]))
+            .collect();
+        ctx.camera = match views[..] {
+            [view] => view,
+            _ => self.overview.update(&self.input, dt, &cars),
+        };
+        match self.state {
+            State::Paused | State::Results => self.audio.mute(),
+            _ => self.audio.update(&self.players[0].car.vehicle, dt),
         }
 
-        // alternate steering is based off the left vector of the car
-        // no energy is added to the car so we need to maintain the magnitude of the velocity
-        // we then add a sideways force depending on the steer direction
-        // then we need to restore the velocity's magnitude
-        //
-        // when displaying the car we need to compute a rotation from the velocity (this is an
-        // issude when speed = 0)
-
-        self.rotation += steer * STEER;
-
-        let velocity = Vec4::new(
-            self.rotation.sin() * self.speed,
-            0.0,
-            self.rotation.cos() * self.speed,
-            0.0,
-        );
+        if let Some(bench) = &mut self.bench {
+            bench.updated(started.elapsed().as_secs_f32());
+        }
+    }
 
-        self.positon += velocity;
+    fn on_event(&mut self, event: &WindowEvent) {
+        self.input.handle_event(event);
+    }
 
-        let local_position = Mat4::from_translation(self.positon.xyz())
-            * Mat4::from_rotation_y(-self.rotation)
-            * Vec4::new(0.0, 0.0, 0.0, 1.0);
+    fn ui(&mut self, ctx: &egui::Context) {
+        match self.state {
+            State::Menu => {
+                if state::menu(ctx, &self.track_name, &mut self.color) {
+                    self.start_race();
+                }
+            }
+            State::Countdown(left) => {
+                self.hud(ctx);
+                state::countdown(ctx, left);
+            }
+            State::Racing => self.hud(ctx),
+            State::Paused => {
+                self.hud(ctx);
+                match state::paused(ctx) {
+                    Some(PauseChoice::Resume) => self.state = State::Racing,
+                    Some(PauseChoice::Quit) => self.state = State::Menu,
+                    None => {}
+                }
+            }
+            State::Results => {
+                let standings: Vec<_> = (0..self.players.len())
+                    .map(|i| (&self.players[i].laps, self.placing(i).0))
+                    .collect();
+                let cars = self.placing(0).1;
+                if state::results(ctx, &standings, cars) {
+                    self.state = State::Menu;
+                }
+            }
+        }
+    }
+}
 
-        // we have a car position
+/// Keyboard bindings and gamepad settings, see [`bindings`].
+const CONTROLS_FILE: &str = "controls.toml";
 
-        self.car.transform(ctx).write().unwrap().update(
-            Mat4::from_translation(local_position.xyz()) * Mat4::from_rotation_y(self.rotation),
-        );
+/// Where the best lap is kept between runs, after the track's name, see [`record_file`].
+const BEST_LAP_FILE: &str = "best_lap.txt";
 
-        // update camera position to be behind car
-        let focus = self.positon.xyz();
-        let eye = focus + Vec3::new(self.rotation.sin() * -2.0, 2.0, self.rotation.cos() * -2.0);
+/// Recording of the best lap, played back as a ghost. Named like [`BEST_LAP_FILE`].
+const GHOST_FILE: &str = "best_lap.replay";
 
-        let eye = self.cam_position.lerp(eye, 0.06);
-        self.cam_position = eye;
-        ctx.camera = Mat4::look_at_rh(eye, focus, Vec3::Y);
-    }
+/// Most players that can race on one machine, see `--players`.
+const MAX_PLAYERS: usize = 2;
 
-    fn on_event(&mut self, event: &WindowEvent) {
-        match event {
-            WindowEvent::KeyboardInput { input, .. } => {
-                // handle key inputs
-                match input.virtual_keycode.unwrap_or(VirtualKeyCode::Kanji) {
-                    VirtualKeyCode::W => self.gas = input.state == ElementState::Pressed,
-                    VirtualKeyCode::S => self.brake = input.state == ElementState::Pressed,
-                    VirtualKeyCode::A => self.left = input.state == ElementState::Pressed,
-                    VirtualKeyCode::D => self.right = input.state == ElementState::Pressed,
-                    _ => {}
-                }
+/// Car color of each player. The first one can be changed in the menu.
+const PLAYER_COLORS: [&str; MAX_PLAYERS] = ["Muscle_Red", "Muscle_Blue"];
+
+/// rivik can't draw transparent meshes, so the ghost stands out by its color instead.
+const GHOST_TEXTURE: &str = "textures/CompactCar_Texture_White.png";
+
+/// File a record for the track loaded from `track_file` is kept in, so every track has its
+/// own, e.g. `track.best_lap.txt` for `track.toml`.
+fn record_file(track_file: &Path, name: &str) -> PathBuf {
+    let track = track_file.file_stem().unwrap_or_default().to_string_lossy();
+    PathBuf::from(format!("{track}.{name}"))
+}
+
+/// Reads the saved ghost, if there is one.
+fn load_ghost(path: &Path) -> Option<Replay> {
+    match Replay::load(path) {
+        Ok(replay) => Some(replay),
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                tracing::warn!("Ignoring unreadable ghost in {}: {e}", path.display());
             }
-            _ => {}
+            None
         }
     }
 }
 
-fn load_mesh(mesh: &str, tex: &str, fmt: ImageFormat) -> Mesh {
-    let mesh = load(mesh, GpuMesh(ObjMesh, mesh::vertex_buffer)).unwrap();
-    let tex = load(tex, GpuTexture(fmt)).unwrap();
-    Mesh::new(mesh, tex)
+/// Track loaded when none is given with `--track`.
+const DEFAULT_TRACK: &str = "track.toml";
+
+/// Car model shared by every car in the race.
+const CAR_FILE: &str = "car.toml";
+
+/// The track description to race on, from `--track <file>` or [`DEFAULT_TRACK`] in the
+/// asset directory.
+fn track_file() -> io::Result<PathBuf> {
+    arg("--track").map_or_else(|| asset_file(DEFAULT_TRACK), |file| Ok(PathBuf::from(file)))
 }
 
-fn load_sky(tex: &str, fmt: ImageFormat) -> SkyMesh {
-    let mesh = load("file:assets/sky.obj", GpuMesh(ObjMesh, mesh::vertex_buffer)).unwrap();
-    let tex = load(tex, GpuTexture(fmt)).unwrap();
-    SkyMesh::new(mesh, tex)
+/// Number of players from `--players <n>`, one if not given.
+fn player_count() -> usize {
+    match arg("--players").map(|n| n.parse()) {
+        None => 1,
+        Some(Ok(n @ 1..=MAX_PLAYERS)) => n,
+        Some(_) => {
+            tracing::warn!("--players takes a number from 1 to {MAX_PLAYERS}");
+            1
+        }
+    }
 }
 
-fn load_car(ctx: &mut rivik::Context, texture: &str) -> Handle<Mesh> {
-    let handle = ctx.insert(load_mesh("file:assets/car.obj", texture, ImageFormat::Png));
+/// Where the benchmark report goes, from `--bench-out <file>` or [`bench::DEFAULT_OUT`].
+fn bench_out() -> PathBuf {
+    arg("--bench-out").map_or_else(|| PathBuf::from(bench::DEFAULT_OUT), PathBuf::from)
+}
+
+/// Number of extra cars and props for a benchmark from `--scale <n>`, none if not given.
+fn scale() -> usize {
+    match arg("--scale").map(|n| n.parse()) {
+        None => 0,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            tracing::warn!("--scale takes a number of copies");
+            0
+        }
+    }
+}
+
+/// Whether `flag` was given on the command line.
+fn flag(flag: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == flag)
+}
 
-    let node = handle.transform(ctx).clone();
+/// Value following `flag` on the command line, if it was given.
+fn arg(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+    }
+    None
+}
+
+/// Directory every relative asset name is resolved against.
+const ASSET_DIR: &str = "assets";
+
+/// Resolves an asset name against [`ASSET_DIR`].
+///
+/// Names that already carry a scheme (`file:`, `http:`, ...) are passed through untouched and
+/// absolute paths are loaded from the filesystem without the root. Everything else is treated
+/// as relative to the root.
+fn asset(name: &str) -> String {
+    let has_scheme = name.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.len() > 1 && scheme.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+
+    if has_scheme {
+        name.to_string()
+    } else if Path::new(name).is_absolute() {
+        format!("file:{name}")
+    } else {
+        format!("file:{ASSET_DIR}/{name}")
+    }
+}
+
+/// Filesystem path of an asset, for data the demo reads itself rather than through rivik.
+///
+/// Resolved the same way as [`asset`], so a name that renders also loads here. Only names that
+/// end up on the local filesystem have a path, others are an error.
+fn asset_file(name: &str) -> io::Result<PathBuf> {
+    asset(name)
+        .strip_prefix("file:")
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{name} is not a local file"),
+            )
+        })
+}
+
+fn load_mesh(mesh: &str, tex: &str) -> io::Result<Mesh> {
+    let mesh = load(&asset(mesh), GpuMesh(ObjMesh, mesh::vertex_buffer))
+        .map_err(|e| asset_error(mesh, e))?;
+    let tex = load(&asset(tex), GpuTexture(image_format(tex))).map_err(|e| asset_error(tex, e))?;
+    Ok(Mesh::new(mesh, tex))
+}
+
+fn load_sky(tex: &str) -> io::Result<SkyMesh> {
+    let mesh = load(&asset(SKY_MESH), GpuMesh(ObjMesh, mesh::vertex_buffer))
+        .map_err(|e| asset_error(SKY_MESH, e))?;
+    let tex = load(&asset(tex), GpuTexture(image_format(tex))).map_err(|e| asset_error(tex, e))?;
+    Ok(SkyMesh::new(mesh, tex))
+}
+
+/// Mesh the sky texture is drawn on.
+const SKY_MESH: &str = "sky.obj";
+
+/// An asset rivik failed to load, named so the message says which one.
+fn asset_error(name: &str, e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{name}: {e}"))
+}
+
+/// Path of the car texture in the given color, e.g. `Muscle_Red`.
+fn car_texture(color: &str) -> String {
+    format!("textures/CompactCar_Texture_{color}.png")
+}
+
+/// Inserts a car body with its wheels attached, all using `texture`.
+fn load_car(ctx: &mut rivik::Context, model: &CarModel, texture: &str) -> io::Result<CarMesh> {
+    let body = ctx.insert(load_mesh(&model.body, texture)?);
+
+    let node = body.transform(ctx).clone();
     let node = &mut *node.write().unwrap();
 
-    ctx.insert_child(
-        node,
-        load_mesh("file:assets/wheel.obj", texture, ImageFormat::Png),
-    )
-    .transform(ctx)
-    .write()
-    .unwrap()
-    .update(Mat4::from_translation(Vec3::new(
-        0.587519, 0.300258, -1.08391,
-    )));
-
-    ctx.insert_child(
-        node,
-        load_mesh("file:assets/wheel.obj", texture, ImageFormat::Png),
-    )
-    .transform(ctx)
-    .write()
-    .unwrap()
-    .update(Mat4::from_translation(Vec3::new(
-        -0.587519, 0.300258, -1.08391,
-    )));
-
-    ctx.insert_child(
-        node,
-        load_mesh("file:assets/wheel.obj", texture, ImageFormat::Png),
-    )
-    .transform(ctx)
-    .write()
-    .unwrap()
-    .update(Mat4::from_translation(Vec3::new(
-        0.60354, 0.299993, 1.35941,
-    )));
-
-    ctx.insert_child(
-        node,
-        load_mesh("file:assets/wheel.obj", texture, ImageFormat::Png),
-    )
-    .transform(ctx)
-    .write()
-    .unwrap()
-    .update(Mat4::from_translation(Vec3::new(
-        -0.60354, 0.299993, 1.35941,
-    )));
-    handle
+    let [a, b, c, d] = model.wheels().map(|offset| -> io::Result<_> {
+        let wheel = ctx.insert_child(node, load_mesh(&model.wheel, texture)?);
+        wheel
+            .transform(ctx)
+            .write()
+            .unwrap()
+            .update(Mat4::from_translation(offset));
+        Ok(wheel)
+    });
+    Ok(CarMesh {
+        body,
+        wheels: [a?, b?, c?, d?],
+    })
 }
 
-fn load_track(ctx: &mut rivik::Context) {
-    ctx.insert(load_mesh(
-        "file:assets/track.obj",
-        "file:assets/textures/track.png",
-        ImageFormat::Png,
-    ));
-    ctx.insert(load_mesh(
-        "file:assets/advertisment.obj",
-        "file:assets/textures/raid.jpg",
-        ImageFormat::Jpeg,
-    ));
-    ctx.insert(load_mesh(
-        "file:assets/billboard_base.obj",
-        "file:assets/textures/track.png",
-        ImageFormat::Png,
-    ));
-    ctx.insert(load_mesh(
-        "file:assets/ground.obj",
-        "file:assets/textures/track.png",
-        ImageFormat::Png,
-    ));
-    ctx.insert(load_mesh(
-        "file:assets/billboard_sign.obj",
-        "file:assets/textures/flag.png",
-        ImageFormat::Png,
-    ));
+/// Inserts every scenery mesh of the track at its placement.
+fn load_scenery(ctx: &mut rivik::Context, track: &Track) -> io::Result<()> {
+    for scenery in &track.meshes {
+        ctx.insert(load_mesh(&scenery.mesh, &scenery.texture)?)
+            .transform(ctx)
+            .write()
+            .unwrap()
+            .update(scenery.transform());
+    }
+    Ok(())
+}
+
+fn load_collision(track: &Track) -> io::Result<TrackCollision> {
+    let sources = track
+        .meshes
+        .iter()
+        .filter(|scenery| scenery.collision != Collision::None)
+        .map(|scenery| {
+            Ok(Source {
+                path: asset_file(&scenery.mesh)?,
+                transform: scenery.transform(),
+                road: scenery.collision == Collision::Road,
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    TrackCollision::load(&sources)
+}
+
+/// Picks the image decoder from a texture's file extension. Anything that isn't a JPEG is
+/// loaded as PNG.
+fn image_format(texture: &str) -> ImageFormat {
+    let ext = Path::new(texture)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => ImageFormat::Jpeg,
+        _ => ImageFormat::Png,
+    }
 }
 
 fn main() {
     set_global_default(Dispatch::new(
-        Registry::default().with(UiSubscriber::default()),
+        Registry::default()
+            .with(UiSubscriber::default())
+            .with(flag("--bench").then_some(bench::SpanTimes)),
     ))
     .unwrap();
     rivik::run::<App>();
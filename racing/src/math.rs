@@ -0,0 +1,125 @@
+//! Vector helpers used by the vehicle model.
+//!
+//! Headings follow the same convention as the rest of the demo: a rotation of `r` radians
+//! around +Y faces the direction `(sin r, 0, cos r)`.
+
+use std::f32::consts::{PI, TAU};
+
+use glam::Vec3;
+
+/// Speeds (units per second) below this are treated as standing still, both when deriving a
+/// heading and when redirecting a velocity.
+const MIN_SPEED: f32 = 0.1;
+
+/// Rate the demo's per-tick tuning constants were originally balanced at.
+const TUNED_HZ: f32 = 60.0;
+
+/// Converts a "move this fraction of the way every tick" factor into the fraction to apply
+/// over `dt` seconds, so exponential approaches like `x += (target - x) * f` behave the
+/// same at any frame rate.
+pub fn damp(fraction_per_tick: f32, dt: f32) -> f32 {
+    1.0 - (1.0 - fraction_per_tick).powf(dt * TUNED_HZ)
+}
+
+/// Unit vector a car with the given heading is facing.
+pub fn forward(heading: f32) -> Vec3 {
+    Vec3::new(heading.sin(), 0.0, heading.cos())
+}
+
+/// Unit vector pointing out of the left side of a car with the given heading.
+pub fn left(heading: f32) -> Vec3 {
+    Vec3::Y.cross(forward(heading))
+}
+
+/// Pushes `velocity` sideways along `left_dir` by `force` without changing its magnitude.
+///
+/// No energy is added to the car, the force only redirects it. A stationary velocity is
+/// returned unchanged, and so is one the force would cancel out exactly, since there is no
+/// direction left to keep the speed in.
+pub fn apply_lateral_force(velocity: Vec3, left_dir: Vec3, force: f32) -> Vec3 {
+    let speed = velocity.length();
+    if speed < MIN_SPEED {
+        return velocity;
+    }
+    let pushed = velocity + left_dir * force;
+    if pushed.length_squared() < MIN_SPEED * MIN_SPEED {
+        return velocity;
+    }
+    pushed.normalize() * speed
+}
+
+/// Heading of a velocity vector projected onto the ground plane.
+///
+/// Near zero speed the direction is meaningless (and `atan2` of tiny values is noise), so
+/// `fallback` is returned instead.
+pub fn heading_from_velocity(velocity: Vec3, fallback: f32) -> f32 {
+    if velocity.x * velocity.x + velocity.z * velocity.z < MIN_SPEED * MIN_SPEED {
+        return fallback;
+    }
+    velocity.x.atan2(velocity.z)
+}
+
+/// Wraps an angle in radians into `[-PI, PI)`, e.g. to find the shortest turn between two
+/// headings.
+pub fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lateral_force_leaves_stationary_car_alone() {
+        let left_dir = left(0.0);
+        assert_eq!(apply_lateral_force(Vec3::ZERO, left_dir, 5.0), Vec3::ZERO);
+
+        let creeping = Vec3::new(0.0, 0.0, MIN_SPEED * 0.5);
+        assert_eq!(apply_lateral_force(creeping, left_dir, 5.0), creeping);
+    }
+
+    #[test]
+    fn lateral_force_cancelling_the_velocity_keeps_it() {
+        // sliding straight left into a force pushing straight right
+        let velocity = left(0.0) * 5.0;
+        assert_eq!(apply_lateral_force(velocity, left(0.0), -5.0), velocity);
+    }
+
+    #[test]
+    fn lateral_force_keeps_speed() {
+        let velocity = Vec3::new(0.0, 0.0, 10.0);
+        let pushed = apply_lateral_force(velocity, left(0.0), 3.0);
+        assert!((pushed.length() - 10.0).abs() < 1e-4);
+        assert!(pushed.x > 0.0);
+    }
+
+    #[test]
+    fn lateral_force_cancelling_velocity_is_not_nan() {
+        // a force exactly opposing the velocity leaves nothing to normalize
+        let velocity = Vec3::new(1.0, 0.0, 0.0);
+        let pushed = apply_lateral_force(velocity, Vec3::NEG_X, 1.0);
+        assert!(!pushed.x.is_nan() && !pushed.y.is_nan() && !pushed.z.is_nan());
+    }
+
+    #[test]
+    fn heading_falls_back_near_zero_speed() {
+        assert_eq!(heading_from_velocity(Vec3::ZERO, 1.5), 1.5);
+        assert_eq!(heading_from_velocity(Vec3::new(1e-6, 0.0, -1e-6), 1.5), 1.5);
+        // vertical movement says nothing about the heading
+        assert_eq!(heading_from_velocity(Vec3::new(0.0, -9.0, 0.0), 1.5), 1.5);
+    }
+
+    #[test]
+    fn heading_follows_velocity() {
+        assert!(heading_from_velocity(Vec3::Z, 1.5).abs() < 1e-6);
+        let heading = heading_from_velocity(Vec3::X * 2.0, 0.0);
+        assert!((heading - PI / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn wrap_angle_stays_in_range() {
+        assert!((wrap_angle(TAU + 0.5) - 0.5).abs() < 1e-5);
+        assert!((wrap_angle(-PI - 0.5) - (PI - 0.5)).abs() < 1e-5);
+        assert_eq!(wrap_angle(PI), -PI);
+    }
+}
@@ -0,0 +1,150 @@
+//! AI opponents.
+//!
+//! Each opponent steers towards a point a little further along the racing line and picks a
+//! target speed from how sharply the line bends further ahead, then works the throttle and
+//! brake to hold it. Every car has its own pace, and rubber-banding speeds up cars that fall
+//! behind the player and holds back the ones that get too far ahead.
+
+use std::io;
+
+use glam::Vec3;
+
+use crate::{
+    car::Car,
+    collision::TrackCollision,
+    math,
+    route::Route,
+    track::{CarModel, Spawn},
+    vehicle::{Controls, TOP_SPEED},
+};
+
+/// Distance along the racing line to the point the car steers towards.
+const STEER_LOOKAHEAD: f32 = 7.0;
+/// Distance along the racing line to the point used to judge the next corner.
+const CORNER_LOOKAHEAD: f32 = 16.0;
+/// Steering deflection per radian between the heading and the steering target.
+const STEER_GAIN: f32 = 2.0;
+/// Fraction of target speed given up per radian the line turns through before the corner
+/// point.
+const CORNER_SLOWDOWN: f32 = 0.3;
+/// Lowest fraction of target speed slowed down to for a corner.
+const MIN_CORNER_SPEED: f32 = 0.45;
+/// Slowest and fastest pace, as a fraction of top speed.
+const PACE: (f32, f32) = (0.88, 0.97);
+/// Gap to the player, along the racing line, at which rubber-banding is at full strength.
+const RUBBER_BAND_RANGE: f32 = 60.0;
+/// Pace added or taken away at full rubber-banding.
+const RUBBER_BAND: f32 = 0.1;
+
+/// Car colors the opponents cycle through.
+const COLORS: [&str; 6] = ["Blue", "Green", "Yellow", "Police", "Taxi", "Orange"];
+
+struct Opponent {
+    car: Car,
+    /// Fraction of top speed this car aims for on the straights.
+    pace: f32,
+}
+
+pub struct Opponents {
+    cars: Vec<Opponent>,
+}
+
+impl Opponents {
+    /// Spawns one opponent per grid slot.
+    pub fn spawn(
+        ctx: &mut rivik::Context,
+        model: &CarModel,
+        route: &Route,
+        grid: &[Spawn],
+    ) -> io::Result<Self> {
+        let cars = grid
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| {
+                let texture = crate::car_texture(COLORS[i % COLORS.len()]);
+                let spread = if grid.len() > 1 {
+                    i as f32 / (grid.len() - 1) as f32
+                } else {
+                    0.5
+                };
+                Ok(Opponent {
+                    car: Car::new(
+                        crate::load_car(ctx, model, &texture)?,
+                        model.chassis(),
+                        route,
+                        slot,
+                    ),
+                    // cars further back on the grid are a bit quicker, so the field mixes
+                    pace: PACE.0 + (PACE.1 - PACE.0) * spread,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self { cars })
+    }
+
+    /// Lines the opponents back up on the grid they were spawned on.
+    pub fn reset(&mut self, route: &Route, grid: &[Spawn]) {
+        for (opponent, slot) in self.cars.iter_mut().zip(grid) {
+            opponent.car.reset(route, slot);
+        }
+    }
+
+    /// Drives every opponent for `dt` seconds. `player_distance` is how far the leading
+    /// player has come, see [`Car::distance`].
+    pub fn update(
+        &mut self,
+        dt: f32,
+        collision: &TrackCollision,
+        route: &Route,
+        player_distance: f32,
+    ) {
+        for opponent in &mut self.cars {
+            let controls = opponent.controls(route, player_distance);
+            opponent.car.drive(&controls, dt, collision, route);
+        }
+    }
+
+    /// Moves every opponent's meshes, see [`Car::sync`].
+    pub fn sync(&self, ctx: &rivik::Context, alpha: f32) {
+        for opponent in &self.cars {
+            opponent.car.sync(ctx, alpha);
+        }
+    }
+
+    pub fn cars(&self) -> impl Iterator<Item = &Car> {
+        self.cars.iter().map(|opponent| &opponent.car)
+    }
+}
+
+impl Opponent {
+    fn controls(&self, route: &Route, player_distance: f32) -> Controls {
+        let gap = (player_distance - self.car.distance(route)) / RUBBER_BAND_RANGE;
+        let pace = (self.pace + gap.clamp(-1.0, 1.0) * RUBBER_BAND).min(1.0);
+        follow_line(&self.car, route, pace)
+    }
+}
+
+/// Controls that drive `car` along the racing line at `pace`, a fraction of top speed.
+pub fn follow_line(car: &Car, route: &Route, pace: f32) -> Controls {
+    let vehicle = &car.vehicle;
+    let distance = car.distance(route);
+    let turn_towards = |target: Vec3| {
+        let to = target - vehicle.position;
+        math::wrap_angle(to.x.atan2(to.z) - vehicle.heading)
+    };
+
+    let steer_error = turn_towards(route.point_at(distance + STEER_LOOKAHEAD));
+    let corner_error = turn_towards(route.point_at(distance + CORNER_LOOKAHEAD));
+
+    let corner = (1.0 - corner_error.abs() * CORNER_SLOWDOWN).max(MIN_CORNER_SPEED);
+    let target_speed = TOP_SPEED * pace * corner;
+
+    let speed = vehicle.velocity.length();
+    Controls {
+        // negative steer is left, which turns the heading up
+        steer: (-steer_error * STEER_GAIN).clamp(-1.0, 1.0),
+        throttle: if speed < target_speed { 1.0 } else { 0.0 },
+        brake: if speed > target_speed * 1.1 { 1.0 } else { 0.0 },
+        handbrake: false,
+    }
+}
@@ -0,0 +1,242 @@
+//! Ghost replays.
+//!
+//! The player's car is sampled at a fixed interval of lap time while driving. When a lap
+//! turns out to be the best one, its recording is saved to disk and played back as a ghost
+//! car on every following lap, in sync with the lap timer.
+//!
+//! Replay files are little-endian binary: the magic `RPLY`, a `u32` format version, then one
+//! record of five `f32`s (time, x, y, z, heading) per sample.
+
+use std::{fs, io, path::Path};
+
+use crate::{car::CarMesh, math, vehicle::Vehicle};
+use glam::{Mat4, Vec3};
+
+/// Seconds of lap time between samples.
+const INTERVAL: f32 = 1.0 / 30.0;
+
+const MAGIC: &[u8; 4] = b"RPLY";
+const VERSION: u32 = 1;
+const SAMPLE_SIZE: usize = 5 * 4;
+
+#[derive(Clone, Copy)]
+struct Sample {
+    time: f32,
+    position: Vec3,
+    heading: f32,
+}
+
+#[derive(Default)]
+pub struct Replay {
+    samples: Vec<Sample>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+
+        let data = fs::read(path)?;
+        let (header, body) = data.split_at(data.len().min(8));
+        if header.len() < 8 || &header[..4] != MAGIC {
+            return Err(invalid("not a replay file"));
+        }
+        if u32::from_le_bytes(header[4..8].try_into().unwrap()) != VERSION {
+            return Err(invalid("unsupported replay version"));
+        }
+        if body.len() % SAMPLE_SIZE != 0 {
+            return Err(invalid("truncated replay"));
+        }
+
+        let samples = body
+            .chunks_exact(SAMPLE_SIZE)
+            .map(|record| {
+                let f = |i: usize| {
+                    let bytes = record[i * 4..][..4].try_into().unwrap();
+                    f32::from_le_bytes(bytes)
+                };
+                Sample {
+                    time: f(0),
+                    position: Vec3::new(f(1), f(2), f(3)),
+                    heading: f(4),
+                }
+            })
+            .collect();
+        Ok(Self { samples })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut data = Vec::with_capacity(8 + self.samples.len() * SAMPLE_SIZE);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        for s in &self.samples {
+            for f in [s.time, s.position.x, s.position.y, s.position.z, s.heading] {
+                data.extend_from_slice(&f.to_le_bytes());
+            }
+        }
+        fs::write(path, data)
+    }
+
+    /// Position and heading `time` seconds into the lap, interpolated between the samples
+    /// around it. Holds the first and last sample outside the recording.
+    fn at(&self, time: f32) -> Option<(Vec3, f32)> {
+        let i = self.samples.partition_point(|s| s.time <= time);
+        let (a, b) = match (i.checked_sub(1), self.samples.get(i)) {
+            (Some(prev), Some(&next)) => (self.samples[prev], next),
+            (None, Some(&first)) => (first, first),
+            (Some(prev), None) => (self.samples[prev], self.samples[prev]),
+            (None, None) => return None,
+        };
+
+        let span = b.time - a.time;
+        let t = if span > 0.0 {
+            ((time - a.time) / span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let heading = a.heading + math::wrap_angle(b.heading - a.heading) * t;
+        Some((a.position.lerp(b.position, t), heading))
+    }
+}
+
+/// Collects samples of the lap in progress.
+#[derive(Default)]
+pub struct Recorder {
+    lap: Replay,
+}
+
+impl Recorder {
+    /// Samples `vehicle` if enough lap time has passed since the last sample. Call every
+    /// step with the current lap time.
+    pub fn record(&mut self, time: f32, vehicle: &Vehicle) {
+        let samples = &mut self.lap.samples;
+        if samples
+            .last()
+            .is_some_and(|last| time - last.time < INTERVAL)
+        {
+            return;
+        }
+        samples.push(Sample {
+            time,
+            position: vehicle.position,
+            heading: vehicle.heading,
+        });
+    }
+
+    /// Hands over the recorded lap and starts an empty one.
+    pub fn finish(&mut self, time: f32, vehicle: &Vehicle) -> Replay {
+        self.lap.samples.push(Sample {
+            time,
+            position: vehicle.position,
+            heading: vehicle.heading,
+        });
+        std::mem::take(&mut self.lap)
+    }
+}
+
+/// A car mesh driven by a replay instead of a vehicle.
+pub struct Ghost {
+    mesh: CarMesh,
+    replay: Option<Replay>,
+}
+
+impl Ghost {
+    pub fn new(mesh: CarMesh, replay: Option<Replay>) -> Self {
+        Self { mesh, replay }
+    }
+
+    pub fn set_replay(&mut self, replay: Replay) {
+        self.replay = Some(replay);
+    }
+
+    /// Moves the ghost to where the replay was `lap_time` seconds into the lap, or out of
+    /// sight if no lap is running.
+    pub fn update(&self, ctx: &rivik::Context, lap_time: Option<f32>) {
+        let pose = lap_time
+            .zip(self.replay.as_ref())
+            .and_then(|(t, r)| r.at(t));
+        let Some((position, heading)) = pose else {
+            self.mesh.park(ctx);
+            return;
+        };
+        self.mesh
+            .body
+            .transform(ctx)
+            .write()
+            .unwrap()
+            .update(Mat4::from_translation(position) * Mat4::from_rotation_y(heading));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// A file in the temp directory that is removed again when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let name = format!("racing-{}-{name}.replay", std::process::id());
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let replay = Replay {
+            samples: vec![
+                Sample {
+                    time: 0.0,
+                    position: Vec3::new(-6.8, 0.0, 17.0),
+                    heading: 0.0,
+                },
+                Sample {
+                    time: 0.5,
+                    position: Vec3::new(-6.5, 0.1, 21.25),
+                    heading: -0.3,
+                },
+            ],
+        };
+        let file = TempFile::new("round-trip");
+        replay.save(&file.0).unwrap();
+        let loaded = Replay::load(&file.0).unwrap();
+
+        assert_eq!(loaded.samples.len(), replay.samples.len());
+        for (a, b) in loaded.samples.iter().zip(&replay.samples) {
+            assert_eq!(a.time, b.time);
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.heading, b.heading);
+        }
+    }
+
+    #[test]
+    fn load_rejects_bad_files() {
+        let file = TempFile::new("bad");
+        let kind = |data: &[u8]| {
+            fs::write(&file.0, data).unwrap();
+            Replay::load(&file.0).err().map(|e| e.kind())
+        };
+
+        let mut header = b"RPLX".to_vec();
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        assert_eq!(kind(&header), Some(io::ErrorKind::InvalidData));
+        assert_eq!(kind(b"RP"), Some(io::ErrorKind::InvalidData));
+
+        let mut future = MAGIC.to_vec();
+        future.extend_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(kind(&future), Some(io::ErrorKind::InvalidData));
+
+        let mut truncated = MAGIC.to_vec();
+        truncated.extend_from_slice(&VERSION.to_le_bytes());
+        truncated.extend_from_slice(&[0; SAMPLE_SIZE - 1]);
+        assert_eq!(kind(&truncated), Some(io::ErrorKind::InvalidData));
+    }
+}
@@ -0,0 +1,227 @@
+//! The racing line.
+//!
+//! A closed Catmull-Rom spline through hand placed waypoints from the track file, see
+//! [`crate::track`]. The spline is flattened into a polyline once at load time. AI cars steer
+//! towards points further along it, and every car's distance along it decides the race
+//! placement.
+
+use glam::Vec3;
+
+/// Polyline points generated per waypoint.
+const SAMPLES_PER_SEGMENT: usize = 8;
+/// How many polyline segments ahead a [`Tracker`] looks for the car each step. Large enough
+/// for a car at top speed during a long frame, small enough that it can't skip to a part of
+/// the track that just happens to be close by.
+const TRACKER_REACH: usize = 4;
+
+pub struct Route {
+    points: Vec<Vec3>,
+    /// Distance along the route to the start of each point, `distances[0]` is zero.
+    distances: Vec<f32>,
+    length: f32,
+}
+
+impl Route {
+    /// Builds the spline through `waypoints`, which need at least three points.
+    pub fn new(waypoints: &[Vec3]) -> Self {
+        let n = waypoints.len();
+        assert!(n >= 3, "a route needs at least 3 points");
+        let mut points = Vec::with_capacity(n * SAMPLES_PER_SEGMENT);
+        for i in 0..n {
+            let p0 = waypoints[(i + n - 1) % n];
+            let p1 = waypoints[i];
+            let p2 = waypoints[(i + 1) % n];
+            let p3 = waypoints[(i + 2) % n];
+            for s in 0..SAMPLES_PER_SEGMENT {
+                let t = s as f32 / SAMPLES_PER_SEGMENT as f32;
+                points.push(catmull_rom(p0, p1, p2, p3, t));
+            }
+        }
+
+        let mut distances = Vec::with_capacity(points.len());
+        let mut length = 0.0;
+        for i in 0..points.len() {
+            distances.push(length);
+            length += points[i].distance(points[(i + 1) % points.len()]);
+        }
+        Self {
+            points,
+            distances,
+            length,
+        }
+    }
+
+    /// Length of one lap.
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    /// Lap a car lined up on the starting grid at `position` begins on, for
+    /// [`Tracker::new`]. A car in the back half of the route is just behind the start line
+    /// and about to begin its first lap, not finish it.
+    pub fn grid_lap(&self, position: Vec3) -> i32 {
+        let (segment, _) = self.closest(position);
+        if segment >= self.points.len() / 2 {
+            -1
+        } else {
+            0
+        }
+    }
+
+    /// Point `distance` units along the route. Wraps around, so distances past the end of
+    /// the lap continue at the start.
+    pub fn point_at(&self, distance: f32) -> Vec3 {
+        let distance = distance.rem_euclid(self.length);
+        let i = self.distances.partition_point(|&d| d <= distance) - 1;
+        let (a, b) = self.segment(i);
+        let len = a.distance(b);
+        if len <= f32::EPSILON {
+            return a;
+        }
+        a.lerp(b, (distance - self.distances[i]) / len)
+    }
+
+    /// Segment closest to `point` on the whole route, with the offset along it, see
+    /// [`Route::project`].
+    fn closest(&self, point: Vec3) -> (usize, f32) {
+        (0..self.points.len())
+            .map(|i| (i, self.project(i, point)))
+            .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+            .map(|(i, (along, _))| (i, along))
+            .unwrap()
+    }
+
+    fn segment(&self, i: usize) -> (Vec3, Vec3) {
+        (self.points[i], self.points[(i + 1) % self.points.len()])
+    }
+
+    /// Where `point` projects onto segment `i`, as an offset from the segment start and the
+    /// squared distance from the segment on the ground plane.
+    fn project(&self, i: usize, point: Vec3) -> (f32, f32) {
+        let (a, b) = self.segment(i);
+        let edge = b - a;
+        let rel = Vec3::new(point.x - a.x, 0.0, point.z - a.z);
+        let len = edge.length();
+        let along = if len <= f32::EPSILON {
+            0.0
+        } else {
+            (rel.dot(edge) / len).clamp(0.0, len)
+        };
+        let closest = a + edge.normalize_or_zero() * along;
+        let offset = Vec3::new(point.x - closest.x, 0.0, point.z - closest.z);
+        (along, offset.length_squared())
+    }
+}
+
+/// Follows a car's progress along a [`Route`], counting laps as it wraps around.
+#[derive(Clone, Copy)]
+pub struct Tracker {
+    segment: usize,
+    along: f32,
+    laps: i32,
+}
+
+impl Tracker {
+    /// Starts tracking a car at `position` on lap `lap`, searching the whole route for the
+    /// closest spot. See [`Route::grid_lap`] for cars on the starting grid.
+    pub fn new(route: &Route, position: Vec3, lap: i32) -> Self {
+        let (segment, along) = route.closest(position);
+        Self {
+            segment,
+            along,
+            laps: lap,
+        }
+    }
+
+    /// Moves the tracker to the closest spot near where the car was last step.
+    pub fn update(&mut self, route: &Route, position: Vec3) {
+        let n = route.points.len();
+        // one segment back so reversing is tracked too
+        let (step, along) = (0..=TRACKER_REACH + 1)
+            .map(|k| (k, route.project((self.segment + n + k - 1) % n, position)))
+            .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+            .map(|(k, (along, _))| (k, along))
+            .unwrap();
+
+        let segment = self.segment + n + step - 1;
+        if segment >= 2 * n {
+            self.laps += 1;
+        } else if segment < n {
+            self.laps -= 1;
+        }
+        self.segment = segment % n;
+        self.along = along;
+    }
+
+    /// Total distance covered along the route, including completed laps. Negative while
+    /// behind the point the route starts at, e.g. on the starting grid.
+    pub fn distance(&self, route: &Route) -> f32 {
+        self.laps as f32 * route.length + route.distances[self.segment] + self.along
+    }
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Route {
+        Route::new(&[
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 40.0),
+            Vec3::new(40.0, 0.0, 40.0),
+            Vec3::new(40.0, 0.0, 0.0),
+        ])
+    }
+
+    /// Drives a tracker from `from` to `to` along the route in small steps.
+    fn drive(route: &Route, from: f32, to: f32) -> Tracker {
+        let mut tracker = Tracker::new(route, route.point_at(from), 0);
+        let steps = ((to - from).abs() as usize).max(1) * 2;
+        for i in 1..=steps {
+            let distance = from + (to - from) * i as f32 / steps as f32;
+            tracker.update(route, route.point_at(distance));
+        }
+        tracker
+    }
+
+    #[test]
+    fn point_at_wraps_around() {
+        let route = square();
+        let a = route.point_at(5.0);
+        assert!(a.distance(route.point_at(5.0 + route.length())) < 1e-3);
+        assert!(a.distance(route.point_at(5.0 - route.length())) < 1e-3);
+    }
+
+    #[test]
+    fn tracker_counts_laps_forwards() {
+        let route = square();
+        let to = route.length() * 2.0 + 10.0;
+        let tracker = drive(&route, 5.0, to);
+        assert_eq!(tracker.laps, 2);
+        assert!((tracker.distance(&route) - to).abs() < 0.1);
+    }
+
+    #[test]
+    fn tracker_counts_laps_backwards() {
+        let route = square();
+        let tracker = drive(&route, 5.0, -10.0);
+        assert_eq!(tracker.laps, -1);
+        assert!((tracker.distance(&route) + 10.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn grid_behind_the_start_line_is_on_lap_minus_one() {
+        let route = square();
+        assert_eq!(route.grid_lap(route.point_at(-3.0)), -1);
+        assert_eq!(route.grid_lap(route.point_at(3.0)), 0);
+    }
+}
@@ -0,0 +1,155 @@
+//! Game flow.
+//!
+//! The demo starts in the menu, where the player picks a car. Starting a race lines everyone
+//! up on the grid for a countdown, then the race runs until a player has driven the track's
+//! number of laps and the results are shown. In two-player races that is whoever finishes
+//! first, the other player is placed by how far they got. Escape pauses and resumes the race.
+//!
+//! Only [`State::Racing`] runs the simulation. Every other state leaves the cars where they
+//! are and draws its own window over the scene.
+
+use rivik::{egui, winit::event::VirtualKeyCode};
+
+use crate::{
+    input::Input,
+    laps::{self, LapTimer},
+};
+
+/// Seconds from starting a race until the cars are let go.
+pub const COUNTDOWN: f32 = 3.0;
+
+/// Key that pauses and resumes the race.
+const PAUSE_KEY: VirtualKeyCode = VirtualKeyCode::Escape;
+
+/// Car textures to pick from in the menu, as the name in `CompactCar_Texture_<name>.png` and
+/// a label.
+const CAR_COLORS: [(&str, &str); 16] = [
+    ("Black", "Black"),
+    ("Blue", "Blue"),
+    ("Brown", "Brown"),
+    ("Gray", "Gray"),
+    ("Green", "Green"),
+    ("Muscle_Blue", "Muscle Blue"),
+    ("Muscle_Orange", "Muscle Orange"),
+    ("Muscle_Red", "Muscle Red"),
+    ("Neon", "Neon"),
+    ("Orange", "Orange"),
+    ("Pink", "Pink"),
+    ("Police", "Police"),
+    ("Red", "Red"),
+    ("Taxi", "Taxi"),
+    ("White", "White"),
+    ("Yellow", "Yellow"),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum State {
+    /// Picking a car before the race.
+    Menu,
+    /// Lined up on the grid, with the seconds left until the start.
+    Countdown(f32),
+    Racing,
+    Paused,
+    /// The player has finished the race.
+    Results,
+}
+
+/// What the player picked in the pause window.
+pub enum PauseChoice {
+    Resume,
+    Quit,
+}
+
+impl State {
+    /// Runs down the countdown and handles pausing. Call once per frame.
+    pub fn advance(self, dt: f32, input: &Input) -> Self {
+        match self {
+            State::Countdown(left) if left <= dt => State::Racing,
+            State::Countdown(left) => State::Countdown(left - dt),
+            State::Racing if input.was_pressed(PAUSE_KEY) => State::Paused,
+            State::Paused if input.was_pressed(PAUSE_KEY) => State::Racing,
+            state => state,
+        }
+    }
+}
+
+/// Draws the main menu with the car selector. Returns whether the race should start.
+pub fn menu(ctx: &egui::Context, track: &str, color: &mut &'static str) -> bool {
+    let label = |name: &str| {
+        CAR_COLORS
+            .iter()
+            .find(|(texture, _)| *texture == name)
+            .map_or(name.to_owned(), |(_, label)| label.to_string())
+    };
+
+    centered("Menu", ctx, |ui| {
+        ui.heading(track);
+        ui.add_space(10.0);
+        egui::ComboBox::from_label("Color")
+            .selected_text(label(color))
+            .show_ui(ui, |ui| {
+                for (texture, label) in CAR_COLORS {
+                    ui.selectable_value(color, texture, label);
+                }
+            });
+        ui.add_space(10.0);
+        ui.button("Start race").clicked()
+    })
+}
+
+/// Shows the seconds left before the start in big numbers.
+pub fn countdown(ctx: &egui::Context, left: f32) {
+    egui::Area::new("Countdown")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.label(egui::RichText::new(format!("{}", left.ceil() as u32)).size(96.0));
+        });
+}
+
+pub fn paused(ctx: &egui::Context) -> Option<PauseChoice> {
+    centered("Paused", ctx, |ui| {
+        ui.heading("Paused");
+        ui.add_space(10.0);
+        if ui.button("Resume").clicked() {
+            Some(PauseChoice::Resume)
+        } else if ui.button("Quit to menu").clicked() {
+            Some(PauseChoice::Quit)
+        } else {
+            None
+        }
+    })
+}
+
+/// Draws the finishing position and lap times of every player, given as their lap timer and
+/// race position. Returns whether to go back to the menu.
+pub fn results(ctx: &egui::Context, standings: &[(&LapTimer, usize)], cars: usize) -> bool {
+    centered("Results", ctx, |ui| {
+        for (i, &(timer, position)) in standings.iter().enumerate() {
+            let place = format!("{} of {cars}", laps::ordinal(position));
+            if standings.len() > 1 {
+                ui.heading(format!("P{} finished {place}", i + 1));
+            } else {
+                ui.heading(format!("Finished {place}"));
+            }
+            ui.add_space(10.0);
+            timer.results(ui);
+            ui.add_space(10.0);
+        }
+        ui.button("Back to menu").clicked()
+    })
+}
+
+/// A fixed window in the middle of the screen.
+fn centered<R: Default>(
+    title: &str,
+    ctx: &egui::Context,
+    add: impl FnOnce(&mut egui::Ui) -> R,
+) -> R {
+    egui::Window::new(title)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .title_bar(false)
+        .resizable(false)
+        .show(ctx, add)
+        .and_then(|response| response.inner)
+        .unwrap_or_default()
+}
@@ -0,0 +1,110 @@
+//! Frame timing.
+//!
+//! `rivik::App::update` is called once per rendered frame without a delta time, so the demo
+//! measures its own. Gameplay doesn't run on frame time though, it runs in fixed ticks (see
+//! [`FixedStep`]) so the same input always plays out the same way regardless of frame rate.
+//! All durations are seconds as `f32`.
+
+use std::time::Instant;
+
+/// Longest frame the simulation will step in one go.
+///
+/// A hitch longer than this (window resize, asset loading, ...) is treated as if it only
+/// took this long, which keeps the car from jumping through the track.
+pub const MAX_DELTA: f32 = 0.1;
+
+/// Measures the wall-clock time between frames.
+pub struct FrameClock {
+    last_frame: Instant,
+    delta: f32,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self {
+            last_frame: Instant::now(),
+            delta: 0.0,
+        }
+    }
+
+    /// Records the start of a new frame. Call exactly once per `update`.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.delta = (now - self.last_frame).as_secs_f32();
+        self.last_frame = now;
+    }
+
+    /// Seconds between the last two ticks.
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// Seconds between the last two ticks, capped at [`MAX_DELTA`].
+    pub fn delta_clamped(&self) -> f32 {
+        self.delta.min(MAX_DELTA)
+    }
+}
+
+/// Length of one simulation tick.
+pub const TICK: f32 = 1.0 / 120.0;
+
+/// Splits variable frame times into whole simulation ticks of [`TICK`] seconds.
+///
+/// Time that doesn't add up to a whole tick is carried over to the next frame. Rendering
+/// happens somewhere between the last two ticks, see [`FixedStep::alpha`].
+pub struct FixedStep {
+    accumulator: f32,
+}
+
+impl FixedStep {
+    pub fn new() -> Self {
+        Self { accumulator: 0.0 }
+    }
+
+    /// Adds `dt` seconds of frame time and returns how many ticks to run for it.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        self.accumulator += dt;
+        let ticks = (self.accumulator / TICK) as u32;
+        self.accumulator -= ticks as f32 * TICK;
+        ticks
+    }
+
+    /// How far rendering is from the previous tick to the latest one, in `[0, 1)`.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / TICK).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn no_ticks_before_a_whole_tick_has_passed() {
+        let mut step = FixedStep::new();
+        assert_eq!(step.advance(0.0), 0);
+        assert_eq!(step.advance(TICK * 0.4), 0);
+        assert!(close(step.alpha(), 0.4));
+    }
+
+    #[test]
+    fn leftover_time_carries_over() {
+        let mut step = FixedStep::new();
+        assert_eq!(step.advance(TICK * 2.5), 2);
+        assert!(close(step.alpha(), 0.5));
+        // the leftover half tick and this three quarters add up to one more
+        assert_eq!(step.advance(TICK * 0.75), 1);
+        assert!(close(step.alpha(), 0.25));
+    }
+
+    #[test]
+    fn long_frames_run_every_tick() {
+        let mut step = FixedStep::new();
+        assert_eq!(step.advance(TICK * 12.25), 12);
+        assert!(close(step.alpha(), 0.25));
+    }
+}
@@ -0,0 +1,227 @@
+//! Track and car descriptions.
+//!
+//! Everything that used to be hardcoded about the circuit lives in a TOML file, so new tracks
+//! don't need a rebuild. Asset names are resolved like every other asset in the demo. A track
+//! file looks like this:
+//!
+//! ```toml
+//! name = "Demo Circuit"
+//! sky = "sky.jpeg"
+//! # length of a race, 3 if left out
+//! laps = 3
+//! # x, z pairs in driving order for the AI, starting on the finish line
+//! racing_line = [[-5.0, 17.0], [-5.0, 45.0], ...]
+//!
+//! # scenery, drawn in order. `collision` is "none" (the default), "walls" or "road".
+//! [[mesh]]
+//! mesh = "track.obj"
+//! texture = "textures/track.png"
+//! collision = "road"
+//!
+//! # props can be placed with a position, a rotation around Y in degrees and a scale
+//! [[mesh]]
+//! mesh = "billboard_base.obj"
+//! texture = "textures/track.png"
+//! position = [10.0, 0.0, 5.0]
+//! rotation = 90.0
+//! collision = "walls"
+//!
+//! # grid slots, pole first. The player starts in the first one.
+//! [[spawn]]
+//! position = [-6.8, 0.0, 17.0]
+//! heading = 0.0
+//!
+//! # trigger volumes in driving order, starting with the finish line, at least two
+//! [[checkpoint]]
+//! center = [-5.0, 0.0, 20.0]
+//! half_extents = [6.0, 3.0, 2.0]
+//! ```
+//!
+//! The car model is described the same way, see [`CarModel`].
+
+use std::{fs, io, path::Path};
+
+use glam::{Mat4, Vec3};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{laps::Checkpoint, route::Route, vehicle::Chassis};
+
+#[derive(Deserialize)]
+pub struct Track {
+    pub name: String,
+    pub sky: String,
+    #[serde(default = "default_laps")]
+    pub laps: usize,
+    #[serde(rename = "mesh", default)]
+    pub meshes: Vec<Scenery>,
+    #[serde(rename = "spawn")]
+    pub spawns: Vec<Spawn>,
+    #[serde(rename = "checkpoint")]
+    pub checkpoints: Vec<CheckpointDef>,
+    pub racing_line: Vec<[f32; 2]>,
+}
+
+impl Track {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let track: Self = read_toml(path)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+        if track.spawns.is_empty() {
+            return Err(invalid("a track needs at least one spawn"));
+        }
+        // with only a finish line, a lap would end as soon as it started
+        if track.checkpoints.len() < 2 {
+            return Err(invalid(
+                "a track needs a finish line and at least one more checkpoint",
+            ));
+        }
+        if track.laps == 0 {
+            return Err(invalid("a race needs at least one lap"));
+        }
+        if track.racing_line.len() < 3 {
+            return Err(invalid("a racing line needs at least 3 points"));
+        }
+        Ok(track)
+    }
+
+    pub fn checkpoints(&self) -> Vec<Checkpoint> {
+        self.checkpoints
+            .iter()
+            .map(|c| Checkpoint::new(c.center.into(), c.half_extents.into()))
+            .collect()
+    }
+
+    pub fn racing_line(&self) -> Vec<Vec3> {
+        self.racing_line
+            .iter()
+            .map(|&[x, z]| Vec3::new(x, 0.0, z))
+            .collect()
+    }
+}
+
+/// A mesh placed in the scene.
+#[derive(Deserialize)]
+pub struct Scenery {
+    pub mesh: String,
+    pub texture: String,
+    #[serde(default)]
+    pub position: [f32; 3],
+    /// Degrees around +Y.
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default = "one")]
+    pub scale: f32,
+    #[serde(default)]
+    pub collision: Collision,
+}
+
+impl Scenery {
+    pub fn transform(&self) -> Mat4 {
+        Mat4::from_translation(self.position.into())
+            * Mat4::from_rotation_y(self.rotation.to_radians())
+            * Mat4::from_scale(Vec3::splat(self.scale))
+    }
+}
+
+/// How a scenery mesh takes part in collision.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Collision {
+    /// Purely visual.
+    #[default]
+    None,
+    /// Steep faces stop the car.
+    Walls,
+    /// Like `Walls`, and raised flat faces count as tarmac.
+    Road,
+}
+
+#[derive(Deserialize)]
+pub struct Spawn {
+    pub position: [f32; 3],
+    /// Degrees around +Y, 0 faces +Z.
+    #[serde(default)]
+    pub heading: f32,
+    /// Lap the car starts on. Slots from the track file are on the grid, which works it out
+    /// from the position, see [`Route::grid_lap`].
+    #[serde(skip)]
+    pub lap: Option<i32>,
+}
+
+impl Spawn {
+    pub fn position(&self) -> Vec3 {
+        self.position.into()
+    }
+
+    pub fn heading(&self) -> f32 {
+        self.heading.to_radians()
+    }
+
+    pub fn lap(&self, route: &Route) -> i32 {
+        self.lap.unwrap_or_else(|| route.grid_lap(self.position()))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CheckpointDef {
+    pub center: [f32; 3],
+    pub half_extents: [f32; 3],
+}
+
+/// The car every racer drives, from its own TOML file:
+///
+/// ```toml
+/// body = "car.obj"
+/// wheel = "wheel.obj"
+/// half_width = 0.97
+/// half_length = 1.97
+/// # hub positions relative to the body, front wheels first
+/// wheels = [[0.6, 0.3, 1.36], [-0.6, 0.3, 1.36], [0.59, 0.3, -1.08], [-0.59, 0.3, -1.08]]
+/// wheel_radius = 0.31
+/// ```
+///
+/// The wheel mesh should be centered on its hub with the axle along X.
+#[derive(Deserialize)]
+pub struct CarModel {
+    pub body: String,
+    pub wheel: String,
+    pub half_width: f32,
+    pub half_length: f32,
+    pub wheels: [[f32; 3]; 4],
+    pub wheel_radius: f32,
+}
+
+impl CarModel {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        read_toml(path)
+    }
+
+    pub fn wheels(&self) -> [Vec3; 4] {
+        self.wheels.map(Vec3::from)
+    }
+
+    pub fn chassis(&self) -> Chassis {
+        Chassis {
+            half_width: self.half_width,
+            half_length: self.half_length,
+            wheels: self.wheels(),
+            wheel_radius: self.wheel_radius,
+        }
+    }
+}
+
+fn read_toml<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    toml::from_str(&fs::read_to_string(path)?).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {e}", path.display()),
+        )
+    })
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn default_laps() -> usize {
+    3
+}
@@ -0,0 +1,127 @@
+//! Arcade vehicle model.
+//!
+//! The car keeps a velocity and a body heading. Steering pushes the velocity sideways, tire
+//! grip bleeds off any velocity that isn't along the body, and the body turns to follow the
+//! direction of travel. When grip is low (handbrake) the body lags behind the velocity and
+//! the car slides.
+
+use glam::{Mat4, Vec3};
+
+use crate::math;
+
+/// Fraction of the way to top speed gained per tick at full throttle.
+const ACC: f32 = 0.05;
+/// Fraction of forward speed lost per tick at full brake.
+const DECEL: f32 = 0.07;
+/// Fraction of forward speed lost per tick to rolling resistance with no throttle.
+const RESIST: f32 = 0.02;
+/// Fraction of forward speed lost per tick with the handbrake pulled.
+const HANDBRAKE_DECEL: f32 = 0.03;
+/// Top speed in units per second.
+pub const TOP_SPEED: f32 = 13.8;
+/// Sideways steering force per unit of forward speed. Because the body turns towards the
+/// velocity while grip pulls the velocity back in line, full lock ends up turning the car at
+/// about `STEER * ALIGN / (GRIP + ALIGN)` radians per second.
+const STEER: f32 = 2.4;
+/// Fraction of sideways velocity the tires cancel per tick.
+const GRIP: f32 = 0.2;
+/// [`GRIP`] with the handbrake pulled.
+const HANDBRAKE_GRIP: f32 = 0.03;
+/// Fraction of the angle between the body and the direction of travel closed per tick.
+const ALIGN: f32 = 0.2;
+
+/// Driver input for a single step, see [`crate::input::Input`] for the ranges.
+#[derive(Clone, Copy, Default)]
+pub struct Controls {
+    pub steer: f32,
+    pub throttle: f32,
+    pub brake: f32,
+    pub handbrake: bool,
+}
+
+/// Dimensions of a car model, see [`crate::track::CarModel`].
+#[derive(Clone, Copy)]
+pub struct Chassis {
+    /// Half the width of the body.
+    pub half_width: f32,
+    /// Half the length of the body.
+    pub half_length: f32,
+    /// Wheel hub positions relative to the body, front wheels first.
+    pub wheels: [Vec3; 4],
+    pub wheel_radius: f32,
+}
+
+pub struct Vehicle {
+    pub chassis: Chassis,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// Rotation of the body around +Y.
+    pub heading: f32,
+}
+
+impl Vehicle {
+    pub fn new(chassis: Chassis, position: Vec3, heading: f32) -> Self {
+        Self {
+            chassis,
+            position,
+            velocity: Vec3::ZERO,
+            heading,
+        }
+    }
+
+    /// Advances the car by `dt` seconds.
+    pub fn step(&mut self, controls: &Controls, dt: f32) {
+        let forward = math::forward(self.heading);
+        let left = math::left(self.heading);
+
+        // steering redirects the velocity without adding energy. The force scales with speed
+        // so the turn rate doesn't depend on how fast the car is going and a stopped car
+        // can't turn at all.
+        let forward_speed = self.velocity.dot(forward);
+        let velocity = math::apply_lateral_force(
+            self.velocity,
+            left,
+            -controls.steer * STEER * forward_speed * dt,
+        );
+
+        // longitudinal and lateral grip are handled separately
+        let mut long = velocity.dot(forward);
+        let mut lat = velocity.dot(left);
+
+        long += (TOP_SPEED - long) * math::damp(ACC * controls.throttle, dt);
+        long -= long * math::damp(RESIST * (1.0 - controls.throttle), dt);
+        long -= long * math::damp(DECEL * controls.brake, dt);
+
+        let grip = if controls.handbrake {
+            long -= long * math::damp(HANDBRAKE_DECEL, dt);
+            HANDBRAKE_GRIP
+        } else {
+            GRIP
+        };
+        lat -= lat * math::damp(grip, dt);
+
+        self.velocity = forward * long + left * lat;
+        self.position += self.velocity * dt;
+
+        // turn the body towards the direction of travel. Going backwards the body should
+        // point away from the velocity instead of flipping around to face it.
+        let travel = if long < 0.0 {
+            -self.velocity
+        } else {
+            self.velocity
+        };
+        let target = math::heading_from_velocity(travel, self.heading);
+        self.heading += math::wrap_angle(target - self.heading) * math::damp(ALIGN, dt);
+    }
+
+    /// Scrubs off speed, e.g. for driving on grass. Uses the same per-tick convention as the
+    /// tuning constants.
+    pub fn drag(&mut self, fraction_per_tick: f32, dt: f32) {
+        self.velocity -= self.velocity * math::damp(fraction_per_tick, dt);
+    }
+
+    /// World transform of the car body.
+    pub fn transform(&self) -> Mat4 {
+        Mat4::from_translation(self.position) * Mat4::from_rotation_y(self.heading)
+    }
+}